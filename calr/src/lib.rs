@@ -1,7 +1,12 @@
-use ansi_term::Style;
-use chrono::{Datelike, Local, NaiveDate};
+use ansi_term::{Color, Style};
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
 use clap::{App, Arg};
-use std::{error::Error, str::FromStr};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -10,11 +15,109 @@ pub struct Config {
     month: Option<u32>,
     year: i32,
     today: NaiveDate,
+    diff: Option<(NaiveDate, NaiveDate)>,
+    paginate: Option<usize>,
+    week_view: bool,
+    leap_years: Option<(i32, i32)>,
+    quarter: Option<u32>,
+    three: bool,
+    format: MonthFormat,
+    output: OutputFormat,
+    nth: Option<(u32, Weekday, i32, u32)>,
+    iso_week: Option<NaiveDate>,
+}
+
+/// The days of a month arranged into weeks starting on Sunday, with `None`
+/// for the slots before the first and after the last day
+#[derive(Debug, PartialEq)]
+pub struct MonthGrid {
+    pub year: i32,
+    pub month: u32,
+    pub weeks: Vec<[Option<u32>; 7]>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// How the days of a month are laid out and highlighted
+#[derive(Debug, Clone, Copy, Default)]
+struct MonthFormat {
+    ordinal_header: bool,
+    julian: bool,
+    week_start: WeekStart,
+    highlight_weekends: bool,
+    no_highlight: bool,
+    highlight_style: HighlightStyle,
+}
+
+/// How today's cell is set apart from the other days
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum HighlightStyle {
+    #[default]
+    Reverse,
+    Bold,
+    Underline,
+    Color(Color),
+}
+
+impl MonthFormat {
+    fn cell_width(self) -> usize {
+        if self.julian {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Seven day cells with a space between each, plus two columns of
+    /// gutter before the next month
+    fn line_width(self) -> usize {
+        self.cell_width() * 7 + 6 + 2
+    }
+}
+
+impl HighlightStyle {
+    fn style(self) -> Style {
+        match self {
+            HighlightStyle::Reverse => Style::new().reverse(),
+            HighlightStyle::Bold => Style::new().bold(),
+            HighlightStyle::Underline => Style::new().underline(),
+            HighlightStyle::Color(color) => color.normal(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum WeekStart {
+    #[default]
+    Sunday,
+    Monday,
+}
+
+impl WeekStart {
+    /// Number of days between the start of the week and `date`
+    fn offset(self, date: NaiveDate) -> u32 {
+        match self {
+            WeekStart::Sunday => date.weekday().num_days_from_sunday(),
+            WeekStart::Monday => date.weekday().num_days_from_monday(),
+        }
+    }
+
+    fn day_names(self) -> [&'static str; 7] {
+        match self {
+            WeekStart::Sunday => ["Su", "Mo", "Tu", "We", "Th", "Fr", "Sa"],
+            WeekStart::Monday => ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"],
+        }
+    }
 }
 
 const MONTH_NAMES: [&str; 12] = [
     "January",
-    "Febrary",
+    "February",
     "March",
     "April",
     "May",
@@ -22,11 +125,11 @@ const MONTH_NAMES: [&str; 12] = [
     "July",
     "August",
     "September",
-    "Octorber",
+    "October",
     "November",
     "December",
 ];
-const LINE_WIDTH: usize = 22;
+const PAGE_BREAK: &str = "\x0c";
 
 pub fn get_args() -> MyResult<Config> {
     let matches = App::new("calr")
@@ -43,7 +146,7 @@ pub fn get_args() -> MyResult<Config> {
         .arg(
             Arg::with_name("year")
                 .value_name("YEAR")
-                .help("Year (1~9999)"),
+                .help("Year (1~9999), or MONTH/YEAR or YEAR-MM"),
         )
         .arg(
             Arg::with_name("year_flag")
@@ -53,39 +156,366 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .conflicts_with_all(&["month", "year"]),
         )
+        .arg(
+            Arg::with_name("diff")
+                .value_name("FROM:TO")
+                .help("Print the number of days between two dates (YYYY-MM-DD:YYYY-MM-DD)")
+                .long("diff")
+                .takes_value(true)
+                .conflicts_with_all(&["month", "year", "year_flag"]),
+        )
+        .arg(
+            Arg::with_name("today")
+                .value_name("YYYY-MM-DD")
+                .help("Use this date as today")
+                .long("today")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("paginate")
+                .value_name("ROWS")
+                .help("Insert a form feed every ROWS lines")
+                .long("paginate")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("week_view")
+                .help("Show only the week containing today on a single line")
+                .long("week-view")
+                .takes_value(false)
+                .conflicts_with_all(&["month", "year", "year_flag", "diff"]),
+        )
+        .arg(
+            Arg::with_name("ordinal_header")
+                .help("Show today's day of the year in the month header")
+                .long("ordinal-header")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("leap_years")
+                .value_name("START:END")
+                .help("List the leap years from START to END")
+                .long("leap-years")
+                .takes_value(true)
+                .conflicts_with_all(&["month", "year", "year_flag", "diff", "week_view"]),
+        )
+        .arg(
+            Arg::with_name("quarter")
+                .value_name("QUARTER")
+                .help("Show the three months of a quarter (1-4)")
+                .long("quarter")
+                .takes_value(true)
+                .conflicts_with_all(&["month", "year_flag", "diff", "week_view", "leap_years"]),
+        )
+        .arg(
+            Arg::with_name("three")
+                .help("Show the previous, current and next month")
+                .short("3")
+                .long("three")
+                .takes_value(false)
+                .conflicts_with_all(&["year_flag", "diff", "week_view", "leap_years", "quarter"]),
+        )
+        .arg(
+            Arg::with_name("output")
+                .value_name("FORMAT")
+                .help("Print the calendar as text or as JSON")
+                .long("format")
+                .possible_values(&["text", "json"])
+                .conflicts_with_all(&["diff", "week_view", "leap_years", "nth"]),
+        )
+        .arg(
+            Arg::with_name("two_digit_year")
+                .help("Read a YEAR of 00-68 as 2000-2068 and 69-99 as 1969-1999")
+                .long("two-digit-year")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("next")
+                .help("Show the month after today's")
+                .long("next")
+                .takes_value(false)
+                .conflicts_with_all(&["month", "year", "year_flag", "prev"]),
+        )
+        .arg(
+            Arg::with_name("prev")
+                .help("Show the month before today's")
+                .long("prev")
+                .takes_value(false)
+                .conflicts_with_all(&["month", "year", "year_flag"]),
+        )
+        .arg(
+            Arg::with_name("julian")
+                .help("Number days by their day of the year")
+                .short("j")
+                .long("julian")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("monday")
+                .help("Start weeks on Monday")
+                .short("M")
+                .long("monday")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("nth")
+                .value_names(&["N", "WEEKDAY", "YYYY-MM"])
+                .help("Print the date of the Nth WEEKDAY of a month")
+                .long("nth")
+                .number_of_values(3)
+                .conflicts_with_all(&[
+                    "month",
+                    "year",
+                    "year_flag",
+                    "diff",
+                    "week_view",
+                    "leap_years",
+                    "quarter",
+                    "three",
+                ]),
+        )
+        .arg(
+            Arg::with_name("iso_week")
+                .value_name("YYYY-Www")
+                .help("Print the dates of an ISO week, Monday to Sunday")
+                .long("iso-week")
+                .conflicts_with_all(&[
+                    "month",
+                    "year",
+                    "year_flag",
+                    "diff",
+                    "week_view",
+                    "leap_years",
+                    "quarter",
+                    "three",
+                    "nth",
+                    "output",
+                ]),
+        )
+        .arg(
+            Arg::with_name("highlight_weekends")
+                .help("Color Saturdays and Sundays")
+                .long("highlight-weekends")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("no_highlight")
+                .help("Do not highlight today")
+                .long("no-highlight")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("highlight")
+                .help("Highlight even when the output is not a terminal")
+                .long("highlight")
+                .takes_value(false)
+                .conflicts_with("no_highlight"),
+        )
+        .arg(
+            Arg::with_name("highlight_style")
+                .value_name("STYLE")
+                .help("How to highlight today: reverse, bold, underline or color:NAME")
+                .long("highlight-style")
+                .conflicts_with("no_highlight"),
+        )
         .get_matches();
 
-    let today = Local::today();
+    let today = match matches.value_of("today") {
+        Some(date) => parse_date(date)?,
+        None => Local::now().date_naive(),
+    };
+    let two_digit_year = matches.is_present("two_digit_year");
+    let color = matches.is_present("highlight") || io::stdout().is_terminal();
     let mut month = matches.value_of("month").map(parse_month).transpose()?;
-    let mut year = matches
+    let (year_month, mut year) = matches
         .value_of("year")
-        .map_or(Ok(today.year()), parse_year)?;
+        .map_or(Ok((None, today.year())), |val| {
+            parse_month_year(val, two_digit_year)
+        })?;
+    if let Some(val) = matches.value_of("year") {
+        let (_, year_text) = split_month_year(val);
+        if !two_digit_year && year_text.len() <= 2 {
+            eprintln!(
+                "calr: reading year {} literally; use --two-digit-year for {}",
+                year,
+                parse_two_digit_year(year_text)?
+            );
+        }
+    }
+    if year_month.is_some() {
+        if month.is_some() {
+            return Err("Month given both with -m and in YEAR".into());
+        }
+        month = year_month;
+    }
     if matches.is_present("year_flag") {
         year = today.year();
         month = None;
+    } else if matches.is_present("next") {
+        let (y, m) = next_month(today.year(), today.month());
+        year = y;
+        month = Some(m);
+    } else if matches.is_present("prev") {
+        let (y, m) = prev_month(today.year(), today.month());
+        year = y;
+        month = Some(m);
+    } else if month.is_none() && !matches.is_present("year") {
+        month = Some(today.month());
     }
 
     Ok(Config {
         month,
         year,
-        today: today.naive_local(),
+        today,
+        diff: matches.value_of("diff").map(parse_diff).transpose()?,
+        paginate: matches.value_of("paginate").map(parse_rows).transpose()?,
+        week_view: matches.is_present("week_view"),
+        leap_years: matches
+            .value_of("leap_years")
+            .map(parse_year_range)
+            .transpose()?,
+        quarter: matches.value_of("quarter").map(parse_quarter).transpose()?,
+        three: matches.is_present("three"),
+        format: MonthFormat {
+            ordinal_header: matches.is_present("ordinal_header"),
+            julian: matches.is_present("julian"),
+            week_start: if matches.is_present("monday") {
+                WeekStart::Monday
+            } else {
+                WeekStart::Sunday
+            },
+            highlight_weekends: matches.is_present("highlight_weekends") && color,
+            no_highlight: matches.is_present("no_highlight") || !color,
+            highlight_style: matches
+                .value_of("highlight_style")
+                .map(parse_highlight_style)
+                .transpose()?
+                .unwrap_or_default(),
+        },
+        output: match matches.value_of("output") {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        },
+        nth: matches
+            .values_of_lossy("nth")
+            .map(|values| parse_nth(&values))
+            .transpose()?,
+        iso_week: matches
+            .value_of("iso_week")
+            .map(parse_iso_week)
+            .transpose()?,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    match config.month {
-        Some(month) => {
-            let lines = format_month(config.year, month, true, config.today);
-            println!("{}", lines.join("\n"));
+    if let Some((from, to)) = config.diff {
+        println!("{}", to.signed_duration_since(from).num_days());
+        return Ok(());
+    }
+    if let Some((n, weekday, year, month)) = config.nth {
+        match nth_weekday(year, month, n, weekday) {
+            Some(date) => println!("{}", date),
+            None => return Err(format!("no {} #{} in {}-{:02}", weekday, n, year, month).into()),
         }
-        None => {
-            let lines = format_month(config.year, 1, true, config.today);
-            println!("{}", lines.join("\n"));
+        return Ok(());
+    }
+    if let Some(monday) = config.iso_week {
+        for date in monday.iter_days().take(7) {
+            println!("{}", date);
+        }
+        return Ok(());
+    }
+    if let Some((start, end)) = config.leap_years {
+        let (start, end) = if start <= end {
+            (start, end)
+        } else {
+            (end, start)
+        };
+        for year in (start..=end).filter(|year| is_leap_year(*year)) {
+            println!("{}", year);
         }
+        return Ok(());
+    }
+    if config.week_view {
+        println!("{}", format_week(config.today, config.format));
+        return Ok(());
+    }
+
+    if config.output == OutputFormat::Json {
+        let mut months: Vec<_> = selected_months(&config)
+            .into_iter()
+            .map(|(year, month)| month_json(year, month, config.today, config.format.week_start))
+            .collect();
+        let json = if months.len() == 1 {
+            months.remove(0)
+        } else {
+            Value::Array(months)
+        };
+        println!("{}", json);
+        return Ok(());
     }
+
+    let lines = if config.three || config.quarter.is_some() {
+        let months: Vec<_> = selected_months(&config)
+            .into_iter()
+            .map(|(year, month)| format_month(year, month, true, config.today, config.format))
+            .collect();
+        join_months(&months, config.format.line_width())
+    } else if let Some(month) = config.month {
+        format_month(config.year, month, true, config.today, config.format)
+    } else {
+        format_year(config.year, config.today, config.format)
+    };
+    let lines = match config.paginate {
+        Some(rows) => paginate(&lines, rows),
+        None => lines,
+    };
+    println!("{}", lines.join("\n"));
     Ok(())
 }
 
+fn format_year(year: i32, today: NaiveDate, format: MonthFormat) -> Vec<String> {
+    let format = MonthFormat {
+        ordinal_header: false,
+        ..format
+    };
+    let months: Vec<_> = (1..=12)
+        .map(|month| format_month(year, month, false, today, format))
+        .collect();
+    let width = format.line_width();
+    let mut lines = vec![format!("{:>width$}", year, width = width * 3 / 2 - 1)];
+    for (i, row) in months.chunks(3).enumerate() {
+        if i > 0 {
+            lines.push("".to_string());
+        }
+        lines.extend(join_months(row, width));
+    }
+    lines
+}
+
+/// Lay out months side by side, padding the shorter ones with blank lines
+fn join_months(months: &[Vec<String>], width: usize) -> Vec<String> {
+    let rows = months.iter().map(Vec::len).max().unwrap_or(0);
+    (0..rows)
+        .map(|row| {
+            months
+                .iter()
+                .map(|lines| match lines.get(row) {
+                    Some(line) => line.to_string(),
+                    None => " ".repeat(width),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn paginate(lines: &[String], rows: usize) -> Vec<String> {
+    lines
+        .chunks(rows)
+        .collect::<Vec<_>>()
+        .join(&PAGE_BREAK.to_string())
+}
+
 fn parse_int<T: FromStr>(val: &str) -> MyResult<T> {
     val.parse::<T>()
         .map_err(|_| format!("Invalid integer \"{}\"", val).into())
@@ -100,6 +530,55 @@ fn parse_year(year: &str) -> MyResult<i32> {
     }
 }
 
+/// Like `parse_year`, but one or two digits name a year in 1969-2068 the
+/// way POSIX `date` reads them
+fn parse_two_digit_year(year: &str) -> MyResult<i32> {
+    match parse_int(year)? {
+        val @ 0..=68 if year.len() <= 2 => Ok(2000 + val),
+        val @ 69..=99 if year.len() <= 2 => Ok(1900 + val),
+        _ => parse_year(year),
+    }
+}
+
+fn parse_month_year(val: &str, two_digit_year: bool) -> MyResult<(Option<u32>, i32)> {
+    let parse_year = if two_digit_year {
+        parse_two_digit_year
+    } else {
+        parse_year
+    };
+    let (month, year) = split_month_year(val);
+    Ok((month.map(parse_month).transpose()?, parse_year(year)?))
+}
+
+/// Splits "MM/YYYY" or "YYYY-MM" into its month and year text
+fn split_month_year(val: &str) -> (Option<&str>, &str) {
+    if let Some((month, year)) = val.split_once('/') {
+        (Some(month), year)
+    } else if let Some((year, month)) = val.split_once('-') {
+        (Some(month), year)
+    } else {
+        (None, val)
+    }
+}
+
+fn parse_year_range(range: &str) -> MyResult<(i32, i32)> {
+    match range.split_once(':') {
+        Some((start, end)) => Ok((parse_year(start)?, parse_year(end)?)),
+        None => Err(format!("Invalid year range \"{}\"", range).into()),
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn parse_rows(rows: &str) -> MyResult<usize> {
+    match parse_int(rows)? {
+        0 => Err(format!("Invalid page size \"{}\"", rows).into()),
+        val => Ok(val),
+    }
+}
+
 fn parse_month(month: &str) -> MyResult<u32> {
     match parse_int(month) {
         Ok(val) => {
@@ -131,44 +610,213 @@ fn parse_month(month: &str) -> MyResult<u32> {
     }
 }
 
-fn format_month(year: i32, month: u32, print_year: bool, today: NaiveDate) -> Vec<String> {
-    let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
-    let mut days: Vec<String> = (1..=first.weekday().num_days_from_sunday())
-        .into_iter()
-        .map(|_| "  ".to_string())
-        .collect();
+fn parse_quarter(quarter: &str) -> MyResult<u32> {
+    let parsed_quarter = parse_int(quarter)?;
+    if (1..=4).contains(&parsed_quarter) {
+        Ok(parsed_quarter)
+    } else {
+        Err(format!(
+            "quarter \"{}\" not in the range 1 through 4",
+            parsed_quarter
+        )
+        .into())
+    }
+}
+
+fn parse_highlight_style(style: &str) -> MyResult<HighlightStyle> {
+    let color = match style {
+        "reverse" => return Ok(HighlightStyle::Reverse),
+        "bold" => return Ok(HighlightStyle::Bold),
+        "underline" => return Ok(HighlightStyle::Underline),
+        _ => style.strip_prefix("color:").map(str::to_lowercase),
+    };
+    let color = match color.as_deref() {
+        Some("black") => Color::Black,
+        Some("red") => Color::Red,
+        Some("green") => Color::Green,
+        Some("yellow") => Color::Yellow,
+        Some("blue") => Color::Blue,
+        Some("purple" | "magenta") => Color::Purple,
+        Some("cyan") => Color::Cyan,
+        Some("white") => Color::White,
+        _ => return Err(format!("Invalid highlight style \"{}\"", style).into()),
+    };
+    Ok(HighlightStyle::Color(color))
+}
+
+fn parse_date(date: &str) -> MyResult<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date \"{}\"", date).into())
+}
+
+fn parse_diff(range: &str) -> MyResult<(NaiveDate, NaiveDate)> {
+    match range.split_once(':') {
+        Some((from, to)) => Ok((parse_date(from)?, parse_date(to)?)),
+        None => Err(format!("Invalid date range \"{}\"", range).into()),
+    }
+}
+
+/// The Monday of an ISO week given as `YYYY-Www`
+fn parse_iso_week(val: &str) -> MyResult<NaiveDate> {
+    let (year, week) = val
+        .split_once("-W")
+        .ok_or_else(|| format!("Invalid ISO week \"{}\"", val))?;
+    let year = parse_year(year)?;
+    let week = parse_int(week)?;
+    NaiveDate::from_isoywd_opt(year, week, Weekday::Mon).ok_or_else(|| {
+        let weeks = NaiveDate::from_ymd_opt(year, 12, 28)
+            .unwrap()
+            .iso_week()
+            .week();
+        format!(
+            "week \"{}\" not in the range 1 through {} for {}",
+            week, weeks, year
+        )
+        .into()
+    })
+}
+
+fn parse_nth(values: &[String]) -> MyResult<(u32, Weekday, i32, u32)> {
+    let n = match parse_int(&values[0])? {
+        0 => return Err(format!("Invalid ordinal \"{}\"", values[0]).into()),
+        n => n,
+    };
+    let weekday = values[1]
+        .parse()
+        .map_err(|_| format!("Invalid weekday \"{}\"", values[1]))?;
+    let (year, month) = values[2]
+        .split_once('-')
+        .ok_or_else(|| format!("Invalid month \"{}\"", values[2]))?;
+    Ok((n, weekday, parse_year(year)?, parse_month(month)?))
+}
+
+/// The date of the `n`th (1-based) `weekday` in a month, if there is one
+fn nth_weekday(year: i32, month: u32, n: u32, weekday: Weekday) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, 1)?
+        .iter_days()
+        .take_while(|date| date.month() == month)
+        .filter(|date| date.weekday() == weekday)
+        .nth(n as usize - 1)
+}
 
-    let is_today = |day: u32| year == today.year() && month == today.month() && day == today.day();
+/// Returns `None` when `month` is not in 1..=12 or the month falls outside
+/// the range of dates chrono can represent
+pub fn month_grid(year: i32, month: u32) -> Option<MonthGrid> {
+    NaiveDate::from_ymd_opt(year, month, 1)?;
+    let (y, m) = next_month(year, month);
+    NaiveDate::from_ymd_opt(y, m, 1)?;
+    Some(build_grid(year, month, WeekStart::Sunday))
+}
 
+fn build_grid(year: i32, month: u32, week_start: WeekStart) -> MonthGrid {
+    let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
     let last = last_day_in_month(year, month);
-    days.extend((first.day()..=last.day()).into_iter().map(|num| {
-        let fmt = format!("{:>2}", num);
-        if is_today(num) {
-            Style::new().reverse().paint(fmt).to_string()
-        } else {
-            fmt
+    let mut slots: Vec<_> = (0..week_start.offset(first)).map(|_| None).collect();
+    slots.extend((1..=last.day()).map(Some));
+    while !slots.len().is_multiple_of(7) {
+        slots.push(None);
+    }
+    MonthGrid {
+        year,
+        month,
+        weeks: slots
+            .chunks(7)
+            .map(|week| week.try_into().unwrap())
+            .collect(),
+    }
+}
+
+/// The (year, month) pairs that the options ask to show, in order. The
+/// months around `three` are left out past either end of years 1-9999.
+fn selected_months(config: &Config) -> Vec<(i32, u32)> {
+    match (config.quarter, config.month) {
+        _ if config.three => {
+            let month = config.month.unwrap_or_else(|| config.today.month());
+            [
+                prev_month(config.year, month),
+                (config.year, month),
+                next_month(config.year, month),
+            ]
+            .into_iter()
+            .filter(|(year, _)| (1..=9999).contains(year))
+            .collect()
+        }
+        (Some(quarter), _) => (quarter * 3 - 2..=quarter * 3)
+            .map(|month| (config.year, month))
+            .collect(),
+        (_, Some(month)) => vec![(config.year, month)],
+        _ => (1..=12).map(|month| (config.year, month)).collect(),
+    }
+}
+
+/// A month as JSON, with each week an array of seven day numbers or nulls
+fn month_json(year: i32, month: u32, today: NaiveDate, week_start: WeekStart) -> Value {
+    json!({
+        "year": year,
+        "month": month,
+        "month_name": MONTH_NAMES[month as usize - 1],
+        "week_start": match week_start {
+            WeekStart::Sunday => "sunday",
+            WeekStart::Monday => "monday",
+        },
+        "weeks": build_grid(year, month, week_start).weeks,
+        "today": today.to_string(),
+    })
+}
+
+fn format_month(
+    year: i32,
+    month: u32,
+    print_year: bool,
+    today: NaiveDate,
+    format: MonthFormat,
+) -> Vec<String> {
+    let cell_width = format.cell_width();
+    let width = format.line_width();
+    let format_day = |day: Option<u32>| match day {
+        None => " ".repeat(cell_width),
+        Some(num) => {
+            let date = NaiveDate::from_ymd_opt(year, month, num).unwrap();
+            let label = if format.julian { date.ordinal() } else { num };
+            let fmt = format!("{:>width$}", label, width = cell_width);
+            if date == today && !format.no_highlight {
+                format.highlight_style.style().paint(fmt).to_string()
+            } else if format.highlight_weekends && is_weekend(date) {
+                Color::Blue.paint(fmt).to_string()
+            } else {
+                fmt
+            }
         }
-    }));
+    };
+
     let mut lines = vec![];
     let month_name = MONTH_NAMES[month as usize - 1];
 
-    lines.push(format!(
-        "{:^20}  ",
-        if print_year {
-            format!("{} {}", month_name, year)
-        } else {
-            month_name.to_string()
-        }
-    ));
+    let mut header = if print_year {
+        format!("{} {}", month_name, year)
+    } else {
+        month_name.to_string()
+    };
+    if format.ordinal_header && year == today.year() && month == today.month() {
+        header = format!("{} (day {})", header, today.ordinal());
+    }
+    lines.push(format!("{:^width$}  ", header, width = width - 2));
 
-    lines.push("Su Mo Tu We Th Fr Sa  ".to_string());
+    let weekdays: Vec<_> = format
+        .week_start
+        .day_names()
+        .iter()
+        .map(|day| format!("{:>width$}", day, width = cell_width))
+        .collect();
+    lines.push(format!("{}  ", weekdays.join(" ")));
 
-    for week in days.chunks(7) {
-        lines.push(format!(
-            "{:width$}  ",
-            week.join(" "),
-            width = LINE_WIDTH - 2
-        ));
+    for week in build_grid(year, month, format.week_start).weeks {
+        let days: Vec<_> = week.iter().map(|day| format_day(*day)).collect();
+        lines.push(format!("{}  ", days.join(" ")));
+    }
+    // Always six week rows so months line up side by side
+    while lines.len() < 8 {
+        lines.push(" ".repeat(width));
     }
     lines
     // "   February 2020      ",
@@ -181,19 +829,58 @@ fn format_month(year: i32, month: u32, print_year: bool, today: NaiveDate) -> Ve
     // "                      ",
 }
 
-fn last_day_in_month(year: i32, month: u32) -> NaiveDate {
-    let (y, m) = if month == 12 {
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+fn format_week(today: NaiveDate, format: MonthFormat) -> String {
+    let start = today - Duration::days(format.week_start.offset(today).into());
+    start
+        .iter_days()
+        .take(7)
+        .map(|date| {
+            let fmt = format!("{} {:>2}", date.weekday(), date.day());
+            if date == today && !format.no_highlight {
+                format.highlight_style.style().paint(fmt).to_string()
+            } else {
+                fmt
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+fn prev_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 1 {
+        (year - 1, 12)
+    } else {
+        (year, month - 1)
+    }
+}
+
+fn next_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 12 {
         (year + 1, 1)
     } else {
         (year, month + 1)
-    };
+    }
+}
+
+fn last_day_in_month(year: i32, month: u32) -> NaiveDate {
+    let (y, m) = next_month(year, month);
     NaiveDate::from_ymd(y, m, 1).pred_opt().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{format_month, last_day_in_month, parse_int, parse_month, parse_year};
-    use chrono::NaiveDate;
+    use super::{
+        format_month, format_week, format_year, is_leap_year, join_months, last_day_in_month,
+        month_grid, next_month, nth_weekday, paginate, parse_date, parse_diff,
+        parse_highlight_style, parse_int, parse_iso_week, parse_month, parse_month_year,
+        parse_quarter, parse_rows, parse_two_digit_year, parse_year, parse_year_range, prev_month,
+        Color, HighlightStyle, MonthFormat, WeekStart,
+    };
+    use chrono::{NaiveDate, Weekday};
 
     #[test]
     fn test_parse_int() {
@@ -256,6 +943,14 @@ mod tests {
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), 1u32);
 
+        let res = parse_month("feb");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 2u32);
+
+        let res = parse_month("oct");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 10u32);
+
         let res = parse_month("0");
         assert!(res.is_err());
         assert_eq!(
@@ -275,6 +970,262 @@ mod tests {
         assert_eq!(res.unwrap_err().to_string(), "Invalid month \"foo\"");
     }
 
+    #[test]
+    fn test_parse_quarter() {
+        let res = parse_quarter("1");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 1u32);
+
+        let res = parse_quarter("4");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 4u32);
+
+        let res = parse_quarter("5");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "quarter \"5\" not in the range 1 through 4"
+        );
+
+        let res = parse_quarter("foo");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid integer \"foo\"");
+    }
+
+    #[test]
+    fn test_month_line_width() {
+        let today = NaiveDate::from_ymd_opt(2021, 4, 7).unwrap();
+        let format = MonthFormat {
+            no_highlight: true,
+            ..Default::default()
+        };
+        assert_eq!(format.line_width(), 22);
+        for line in format_month(2020, 2, true, today, format) {
+            assert_eq!(line.chars().count(), 22);
+        }
+
+        let julian = MonthFormat {
+            julian: true,
+            ..format
+        };
+        assert_eq!(julian.line_width(), 29);
+        for line in format_month(2020, 2, true, today, julian) {
+            assert_eq!(line.chars().count(), 29);
+        }
+    }
+
+    #[test]
+    fn test_join_months() {
+        let left = vec!["a".repeat(22), "b".repeat(22)];
+        let right = vec!["c".repeat(22)];
+        assert_eq!(
+            join_months(&[left, right], 22),
+            [
+                format!("{}{}", "a".repeat(22), "c".repeat(22)),
+                format!("{}{}", "b".repeat(22), " ".repeat(22)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_highlight_style() {
+        let res = parse_highlight_style("bold");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), HighlightStyle::Bold);
+
+        let res = parse_highlight_style("color:red");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), HighlightStyle::Color(Color::Red));
+
+        let res = parse_highlight_style("color:mauve");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Invalid highlight style \"color:mauve\""
+        );
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let res = parse_date("2021-02-28");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), NaiveDate::from_ymd_opt(2021, 2, 28).unwrap());
+
+        let res = parse_date("2021-02-29");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid date \"2021-02-29\"");
+    }
+
+    #[test]
+    fn test_parse_iso_week() {
+        let res = parse_iso_week("2021-W15");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), NaiveDate::from_ymd_opt(2021, 4, 12).unwrap());
+
+        let res = parse_iso_week("2020-W53");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), NaiveDate::from_ymd_opt(2020, 12, 28).unwrap());
+
+        let res = parse_iso_week("2021-W53");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "week \"53\" not in the range 1 through 52 for 2021"
+        );
+
+        let res = parse_iso_week("2021-W0");
+        assert!(res.is_err());
+
+        let res = parse_iso_week("2021-15");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid ISO week \"2021-15\"");
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let res = parse_diff("2021-01-01:2021-12-31");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            (
+                NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2021, 12, 31).unwrap()
+            )
+        );
+
+        let res = parse_diff("2021-01-01");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Invalid date range \"2021-01-01\""
+        );
+
+        let res = parse_diff("2021-01-01:foo");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid date \"foo\"");
+    }
+
+    #[test]
+    fn test_nth_weekday() {
+        assert_eq!(
+            nth_weekday(2021, 4, 2, Weekday::Mon),
+            NaiveDate::from_ymd_opt(2021, 4, 12)
+        );
+        assert_eq!(
+            nth_weekday(2021, 4, 5, Weekday::Fri),
+            NaiveDate::from_ymd_opt(2021, 4, 30)
+        );
+        assert_eq!(nth_weekday(2021, 2, 5, Weekday::Fri), None);
+    }
+
+    #[test]
+    fn test_parse_month_year() {
+        let res = parse_month_year("3/2020", false);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (Some(3), 2020));
+
+        let res = parse_month_year("2020-03", false);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (Some(3), 2020));
+
+        let res = parse_month_year("mar/2020", false);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (Some(3), 2020));
+
+        let res = parse_month_year("2020", false);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (None, 2020));
+
+        let res = parse_month_year("13/2020", false);
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "month \"13\" not in the range 1 through 12"
+        );
+
+        let res = parse_month_year("3/foo", false);
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid integer \"foo\"");
+    }
+
+    #[test]
+    fn test_parse_two_digit_year() {
+        for (year, expected) in [("20", 2020), ("00", 2000), ("69", 1969), ("99", 1999)] {
+            let res = parse_two_digit_year(year);
+            assert!(res.is_ok());
+            assert_eq!(res.unwrap(), expected);
+        }
+
+        let res = parse_two_digit_year("100");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 100);
+
+        let res = parse_two_digit_year("020");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 20);
+
+        let res = parse_month_year("3/20", true);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (Some(3), 2020));
+
+        let res = parse_month_year("20", false);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (None, 20));
+    }
+
+    #[test]
+    fn test_parse_year_range() {
+        let res = parse_year_range("1896:1912");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (1896, 1912));
+
+        let res = parse_year_range("1896");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid year range \"1896\"");
+
+        let res = parse_year_range("1896:0");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "year \"0\" not in the range 1 through 9999"
+        );
+    }
+
+    #[test]
+    fn test_is_leap_year() {
+        assert!(is_leap_year(1896));
+        assert!(!is_leap_year(1900));
+        assert!(is_leap_year(2000));
+        assert!(is_leap_year(2020));
+        assert!(!is_leap_year(2021));
+    }
+
+    #[test]
+    fn test_parse_rows() {
+        let res = parse_rows("10");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 10);
+
+        let res = parse_rows("0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid page size \"0\"");
+
+        let res = parse_rows("foo");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "Invalid integer \"foo\"");
+    }
+
+    #[test]
+    fn test_paginate() {
+        let lines: Vec<String> = (1..=5).map(|n| n.to_string()).collect();
+        assert_eq!(
+            paginate(&lines, 2),
+            ["1", "2", "\x0c", "3", "4", "\x0c", "5"]
+        );
+        assert_eq!(paginate(&lines, 5), ["1", "2", "3", "4", "5"]);
+        assert_eq!(paginate(&[], 3), Vec::<String>::new());
+    }
+
     #[test]
     fn test_format_month() {
         let today = NaiveDate::from_ymd(0, 1, 1);
@@ -288,7 +1239,10 @@ mod tests {
             "23 24 25 26 27 28 29  ",
             "                      ",
         ];
-        assert_eq!(format_month(2020, 2, true, today), leap_february);
+        assert_eq!(
+            format_month(2020, 2, true, today, MonthFormat::default()),
+            leap_february
+        );
 
         let may = vec![
             "        May           ",
@@ -300,7 +1254,10 @@ mod tests {
             "24 25 26 27 28 29 30  ",
             "31                    ",
         ];
-        assert_eq!(format_month(2020, 5, false, today), may);
+        assert_eq!(
+            format_month(2020, 5, false, today, MonthFormat::default()),
+            may
+        );
 
         let april_hl = vec![
             "     April 2021       ",
@@ -313,12 +1270,164 @@ mod tests {
             "                      ",
         ];
         let today = NaiveDate::from_ymd(2021, 4, 7);
-        assert_eq!(format_month(2021, 4, true, today), april_hl);
+        assert_eq!(
+            format_month(2021, 4, true, today, MonthFormat::default()),
+            april_hl
+        );
+    }
+
+    #[test]
+    fn test_month_grid() {
+        assert_eq!(month_grid(2020, 0), None);
+        assert_eq!(month_grid(2020, 13), None);
+        let grid = month_grid(2020, 2).unwrap();
+        assert_eq!((grid.year, grid.month), (2020, 2));
+        assert_eq!(grid.weeks.len(), 5);
+        assert_eq!(grid.weeks[0], [None, None, None, None, None, None, Some(1)]);
+        assert_eq!(
+            grid.weeks[4],
+            [
+                Some(23),
+                Some(24),
+                Some(25),
+                Some(26),
+                Some(27),
+                Some(28),
+                Some(29)
+            ]
+        );
+        let days: Vec<_> = grid.weeks.iter().flatten().flatten().collect();
+        assert_eq!(days.len(), 29);
+        assert_eq!(days.first(), Some(&&1));
+        assert_eq!(days.last(), Some(&&29));
+    }
+
+    #[test]
+    fn test_format_month_week_start() {
+        // August 2021 starts on a Sunday
+        let today = NaiveDate::from_ymd_opt(2021, 4, 7).unwrap();
+        let lines = format_month(2021, 8, true, today, MonthFormat::default());
+        assert_eq!(lines[1], "Su Mo Tu We Th Fr Sa  ");
+        assert_eq!(lines[2], " 1  2  3  4  5  6  7  ");
+
+        let monday = MonthFormat {
+            week_start: WeekStart::Monday,
+            ..MonthFormat::default()
+        };
+        let lines = format_month(2021, 8, true, today, monday);
+        assert_eq!(lines[1], "Mo Tu We Th Fr Sa Su  ");
+        assert_eq!(lines[2], "                   1  ");
+        assert_eq!(lines[3], " 2  3  4  5  6  7  8  ");
+        assert_eq!(lines[7], "30 31                 ");
+    }
+
+    #[test]
+    fn test_format_month_highlight_weekends() {
+        // Today is Saturday, April 10th
+        let today = NaiveDate::from_ymd_opt(2021, 4, 10).unwrap();
+        let weekends = MonthFormat {
+            highlight_weekends: true,
+            ..MonthFormat::default()
+        };
+        let lines = format_month(2021, 4, true, today, weekends);
+        assert_eq!(lines[1], "Su Mo Tu We Th Fr Sa  ");
+        assert_eq!(lines[2], "             1  2 \u{1b}[34m 3\u{1b}[0m  ");
+        assert_eq!(
+            lines[3],
+            "\u{1b}[34m 4\u{1b}[0m  5  6  7  8  9 \u{1b}[7m10\u{1b}[0m  "
+        );
+        assert_eq!(lines[6], "\u{1b}[34m25\u{1b}[0m 26 27 28 29 30     ");
+    }
+
+    #[test]
+    fn test_format_month_no_highlight() {
+        let today = NaiveDate::from_ymd_opt(2021, 4, 7).unwrap();
+        let plain = MonthFormat {
+            no_highlight: true,
+            ..MonthFormat::default()
+        };
+        let lines = format_month(2021, 4, true, today, plain);
+        assert_eq!(lines[3], " 4  5  6  7  8  9 10  ");
+        assert!(lines.iter().all(|line| !line.contains('\u{1b}')));
+        assert!(!format_week(today, plain).contains('\u{1b}'));
     }
+
+    #[test]
+    fn test_format_month_julian() {
+        let today = NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+        let julian = MonthFormat {
+            julian: true,
+            ..MonthFormat::default()
+        };
+        let lines = format_month(2020, 2, true, today, julian);
+        assert_eq!(lines[0], "       February 2020         ");
+        assert_eq!(lines[1], " Su  Mo  Tu  We  Th  Fr  Sa  ");
+        assert_eq!(lines[2], "                         32  ");
+        assert_eq!(lines[6], " 54  55  56  57  58  59 \u{1b}[7m 60\u{1b}[0m  ");
+        assert!(lines.iter().all(|line| !line.contains(" 29 ")));
+    }
+
+    #[test]
+    fn test_format_month_ordinal_header() {
+        let today = NaiveDate::from_ymd_opt(2021, 4, 7).unwrap();
+        let ordinal = MonthFormat {
+            ordinal_header: true,
+            ..MonthFormat::default()
+        };
+        let lines = format_month(2021, 4, true, today, ordinal);
+        assert_eq!(lines[0], "April 2021 (day 97)   ");
+
+        // Only the month containing today gets the ordinal
+        let lines = format_month(2021, 5, true, today, ordinal);
+        assert_eq!(lines[0], "      May 2021        ");
+        let lines = format_month(2020, 4, true, today, ordinal);
+        assert_eq!(lines[0], "     April 2020       ");
+    }
+
+    #[test]
+    fn test_format_week() {
+        let today = NaiveDate::from_ymd_opt(2021, 4, 7).unwrap();
+        assert_eq!(
+            format_week(today, MonthFormat::default()),
+            "Sun  4  Mon  5  Tue  6  \u{1b}[7mWed  7\u{1b}[0m  Thu  8  Fri  9  Sat 10"
+        );
+
+        // A week spanning the new year
+        let today = NaiveDate::from_ymd_opt(2021, 1, 2).unwrap();
+        assert_eq!(
+            format_week(today, MonthFormat::default()),
+            "Sun 27  Mon 28  Tue 29  Wed 30  Thu 31  Fri  1  \u{1b}[7mSat  2\u{1b}[0m"
+        );
+    }
+
+    #[test]
+    fn test_format_year() {
+        let today = NaiveDate::from_ymd_opt(2021, 4, 7).unwrap();
+        let lines = format_year(2020, today, MonthFormat::default());
+        assert_eq!(lines.len(), 36);
+        assert_eq!(lines[0], "                            2020");
+        assert_eq!(
+            lines[3],
+            "          1  2  3  4                     1   1  2  3  4  5  6  7  "
+        );
+        assert_eq!(lines[9], "");
+        assert!(lines[1..]
+            .iter()
+            .all(|line| line.is_empty() || line.chars().count() == 66));
+    }
+
     #[test]
     fn test_last_day_in_month() {
         assert_eq!(last_day_in_month(2020, 1), NaiveDate::from_ymd(2020, 1, 31));
         assert_eq!(last_day_in_month(2020, 2), NaiveDate::from_ymd(2020, 2, 29));
         assert_eq!(last_day_in_month(2020, 4), NaiveDate::from_ymd(2020, 4, 30));
     }
+
+    #[test]
+    fn test_prev_next_month() {
+        assert_eq!(prev_month(2021, 5), (2021, 4));
+        assert_eq!(prev_month(2021, 1), (2020, 12));
+        assert_eq!(next_month(2021, 5), (2021, 6));
+        assert_eq!(next_month(2021, 12), (2022, 1));
+    }
 }
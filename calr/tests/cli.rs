@@ -207,3 +207,481 @@ fn year() -> TestResult {
     assert_eq!(lines.len(), 37);
     Ok(())
 }
+
+// --------------------------------------------------
+fn run_diff(range: &str, expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--diff", range])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", expected));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn diff_forward() -> TestResult {
+    run_diff("2021-01-01:2021-12-31", "364")
+}
+
+// --------------------------------------------------
+#[test]
+fn diff_reversed() -> TestResult {
+    run_diff("2021-12-31:2021-01-01", "-364")
+}
+
+// --------------------------------------------------
+#[test]
+fn diff_same_day() -> TestResult {
+    run_diff("2020-02-29:2020-02-29", "0")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_diff() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--diff", "2021-01-01:2021-13-01"])
+        .assert()
+        .failure()
+        .stderr("Invalid date \"2021-13-01\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn today_override() -> TestResult {
+    for (today, day) in &[("2021-04-07", " 7"), ("2021-04-15", "15")] {
+        let expected = format!("\u{1b}[7m{}\u{1b}[0m", day);
+        let cmd = Command::cargo_bin(PRG)?
+            .args(["-m", "4", "2021", "--today", today, "--highlight"])
+            .assert()
+            .success();
+        let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+        assert_eq!(stdout.matches("\u{1b}[7m").count(), 1);
+        assert!(stdout.contains(&expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_today() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--today", "2021-02-30"])
+        .assert()
+        .failure()
+        .stderr("Invalid date \"2021-02-30\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paginate_rows() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-m", "4", "2021", "--paginate", "3"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let pages: Vec<_> = stdout.split("\n\x0c\n").collect();
+    assert!(pages.len() > 1);
+    for page in &pages[..pages.len() - 1] {
+        assert_eq!(page.lines().count(), 3);
+    }
+    assert!(pages[pages.len() - 1].lines().count() <= 3);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn week_view() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--week-view", "--today", "2020-03-01", "--highlight"])
+        .assert()
+        .success()
+        .stdout("\u{1b}[7mSun  1\u{1b}[0m  Mon  2  Tue  3  Wed  4  Thu  5  Fri  6  Sat  7\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ordinal_header() -> TestResult {
+    for (month, header) in &[("4", "April 2021 (day 97)"), ("5", "May 2021  ")] {
+        let cmd = Command::cargo_bin(PRG)?
+            .args([
+                "-m",
+                month,
+                "2021",
+                "--today",
+                "2021-04-07",
+                "--ordinal-header",
+            ])
+            .assert()
+            .success();
+        let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+        assert!(stdout.lines().next().unwrap().contains(header));
+        assert_eq!(stdout.contains("(day"), *month == "4");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn leap_years() -> TestResult {
+    for range in &["1896:1912", "1912:1896"] {
+        Command::cargo_bin(PRG)?
+            .args(["--leap-years", range])
+            .assert()
+            .success()
+            .stdout("1896\n1904\n1908\n1912\n");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quarter_1() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--quarter", "1", "2021"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let header = stdout.lines().next().unwrap();
+    let jan = header.find("January").unwrap();
+    let feb = header.find("Feb").unwrap();
+    let mar = header.find("March").unwrap();
+    assert!(jan < feb && feb < mar);
+    assert_eq!(header.len(), 66);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_quarter_5() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--quarter", "5"])
+        .assert()
+        .failure()
+        .stderr("quarter \"5\" not in the range 1 through 4\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_three(month: &str, year: &str, expected: &[&str]) -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-3", "-m", month, year])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let header = stdout.lines().next().unwrap();
+    let positions: Vec<_> = expected
+        .iter()
+        .map(|month| header.find(month).unwrap())
+        .collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(header.len(), 22 * expected.len());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_january() -> TestResult {
+    run_three(
+        "1",
+        "2021",
+        &["December 2020", "January 2021", "February 2021"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn three_january_year_1() -> TestResult {
+    run_three("1", "1", &["January 1 ", "February 1 "])
+}
+
+// --------------------------------------------------
+#[test]
+fn three_december() -> TestResult {
+    run_three(
+        "12",
+        "2021",
+        &["November 2021", "December 2021", "January 2022"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn three_december_year_9999() -> TestResult {
+    run_three("12", "9999", &["November 9999", "December 9999"])
+}
+
+// --------------------------------------------------
+#[test]
+fn julian_leap_day() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([
+            "-j",
+            "-m",
+            "2",
+            "2020",
+            "--today",
+            "2020-02-29",
+            "--highlight",
+        ])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[1], " Su  Mo  Tu  We  Th  Fr  Sa  ");
+    assert!(lines[6].ends_with("59 \u{1b}[7m 60\u{1b}[0m  "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn monday_start() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--monday", "-m", "8", "2021"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[1], "Mo Tu We Th Fr Sa Su  ");
+    assert_eq!(lines[2], "                   1  ");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn nth_weekday() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--nth", "2", "monday", "2021-04"])
+        .assert()
+        .success()
+        .stdout("2021-04-12\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_nth_weekday_missing() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--nth", "5", "fri", "2021-02"])
+        .assert()
+        .failure()
+        .stderr("no Fri #5 in 2021-02\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_weekends() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([
+            "-m",
+            "4",
+            "2021",
+            "--today",
+            "2021-04-10",
+            "--highlight-weekends",
+            "--highlight",
+        ])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    // Every weekend day but today is colored
+    assert_eq!(stdout.matches("\u{1b}[34m").count(), 7);
+    assert!(stdout.contains("\u{1b}[7m10\u{1b}[0m"));
+
+    // Output to a pipe is plain unless highlighting is forced on
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-m", "4", "2021", "--highlight-weekends"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert!(!stdout.contains('\u{1b}'));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_highlight() -> TestResult {
+    // Output to a pipe is plain unless highlighting is forced on
+    for args in [
+        &["--today", "2021-04-07"][..],
+        &["--today", "2021-04-07", "--no-highlight"],
+    ] {
+        let cmd = Command::cargo_bin(PRG)?
+            .args(["-m", "4", "2021"])
+            .args(args)
+            .assert()
+            .success();
+        let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+        assert!(!stdout.contains('\u{1b}'));
+        assert!(stdout.contains(" 6  7  8 "));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn next_and_prev() -> TestResult {
+    for (flag, today, header) in &[
+        ("--next", "2021-12-15", "January 2022"),
+        ("--prev", "2021-01-15", "December 2020"),
+        ("--next", "2021-04-15", "May 2021"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "--today", today])
+            .assert()
+            .success()
+            .stdout(predicate::str::starts_with(format!("{:^20}  \n", header)));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_next_with_month() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--next", "-m", "4"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_style() -> TestResult {
+    for (style, code) in &[
+        ("bold", "1"),
+        ("underline", "4"),
+        ("color:red", "31"),
+        ("reverse", "7"),
+    ] {
+        let expected = format!("\u{1b}[{}m 7\u{1b}[0m", code);
+        let cmd = Command::cargo_bin(PRG)?
+            .args(["-m", "4", "2021", "--today", "2021-04-07", "--highlight"])
+            .arg(format!("--highlight-style={}", style))
+            .assert()
+            .success();
+        let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+        assert!(stdout.contains(&expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_highlight_style() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--highlight-style=color:mauve"])
+        .assert()
+        .failure()
+        .stderr("Invalid highlight style \"color:mauve\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn two_digit_year() -> TestResult {
+    for (year, header) in &[("20", "2020"), ("69", "1969"), ("99", "1999")] {
+        Command::cargo_bin(PRG)?
+            .args(["--two-digit-year", "-m", "1", year])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!("January {}", header)))
+            .stderr("");
+    }
+
+    Command::cargo_bin(PRG)?
+        .args(["-m", "1", "20"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("January 20 "))
+        .stderr("calr: reading year 20 literally; use --two-digit-year for 2020\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-m", "1", "0050"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("January 50 "))
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_month() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([
+            "-m",
+            "2",
+            "2020",
+            "--today",
+            "2020-02-14",
+            "--format",
+            "json",
+        ])
+        .assert()
+        .success();
+    let json: serde_json::Value = serde_json::from_slice(&cmd.get_output().stdout)?;
+    assert_eq!(json["year"], 2020);
+    assert_eq!(json["month"], 2);
+    assert_eq!(json["month_name"], "February");
+    assert_eq!(json["today"], "2020-02-14");
+    assert_eq!(json["week_start"], "sunday");
+    let weeks = json["weeks"].as_array().unwrap();
+    assert_eq!(weeks.len(), 5);
+    assert!(weeks.iter().all(|week| week.as_array().unwrap().len() == 7));
+    assert_eq!(weeks[0][6], 1);
+    assert!(weeks[0][5].is_null());
+
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-m", "2", "2020", "--monday", "--format=json"])
+        .assert()
+        .success();
+    let json: serde_json::Value = serde_json::from_slice(&cmd.get_output().stdout)?;
+    assert_eq!(json["week_start"], "monday");
+    assert_eq!(json["weeks"][0][5], 1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_year() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["2020", "--format", "json"])
+        .assert()
+        .success();
+    let json: serde_json::Value = serde_json::from_slice(&cmd.get_output().stdout)?;
+    let months = json.as_array().unwrap();
+    assert_eq!(months.len(), 12);
+    assert_eq!(months[11]["month_name"], "December");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn iso_week() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--iso-week", "2021-W15"])
+        .assert()
+        .success()
+        .stdout(
+            "2021-04-12\n2021-04-13\n2021-04-14\n2021-04-15\n\
+             2021-04-16\n2021-04-17\n2021-04-18\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_iso_week() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--iso-week", "2021-W53"])
+        .assert()
+        .failure()
+        .stderr("week \"53\" not in the range 1 through 52 for 2021\n");
+    Ok(())
+}
@@ -1,53 +1,375 @@
 use clap::{App, Arg};
+use regex::{NoExpand, Regex};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::env;
 use std::error::Error;
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
-pub fn run(config: Config) -> MyResult<()> {
-    // dbg!(config);
-    for filename in config.files {
-        match open(&filename) {
-            Err(err) => eprint!("{} : {}", filename, err),
-            Ok(file) => {
-                let mut last_num = 0;
-                for (line_num, line_result) in file.lines().enumerate() {
-                    let line = line_result?;
-                    if config.number_lines {
-                        println!("{:6}\t{}", line_num + 1, line);
-                    } else if config.number_nonblank_lines {
-                        if !line.is_empty() {
-                            last_num += 1;
-                            println!("{:6}\t{}", last_num, line);
-                        } else {
-                            println!("");
+/// Print the files, returning how many of them could not be read
+pub fn run(config: Config) -> MyResult<usize> {
+    cat(&config, &mut io::stdout().lock())
+}
+
+/// Build the text `run` would print, one newline-terminated line per output line
+pub fn cat_to_string(config: &Config) -> MyResult<String> {
+    let mut out = vec![];
+    cat(config, &mut out)?;
+    Ok(String::from_utf8(out)?)
+}
+
+/// Write the output for every file to `out` as it is read, reporting files
+/// that cannot be opened or read on stderr and carrying on with the rest
+fn cat(config: &Config, out: &mut impl Write) -> MyResult<usize> {
+    let mut failed = 0;
+    for filename in &config.files {
+        let mut next_num = Some(config.number_from);
+        let mut offset = 0;
+        let mut stream = match open(filename) {
+            Err(err) => {
+                eprintln!("{} : {}", filename, err);
+                failed += 1;
+                continue;
+            }
+            Ok(file) => Some(file),
+        };
+        // Reversing needs the whole input, and so does replaying it for
+        // --repeat since stdin cannot be opened a second time
+        let buffered = if config.reverse {
+            Some(reverse_lines(stream.take().unwrap()))
+        } else if config.repeat > 1 {
+            Some(read_all(stream.take().unwrap()))
+        } else {
+            None
+        };
+        let buffered = match buffered.transpose() {
+            Err(err) => {
+                eprintln!("{} : {}", filename, err);
+                failed += 1;
+                continue;
+            }
+            Ok(buffered) => buffered,
+        };
+        'repeat: for _ in 0..config.repeat {
+            if config.repeat_reset {
+                next_num = Some(config.number_from);
+                offset = 0;
+            }
+            let mut file: Box<dyn BufRead + '_> = match (&buffered, stream.take()) {
+                (Some(buf), _) => Box::new(buf.as_slice()),
+                (None, Some(file)) => file,
+                (None, None) => break,
+            };
+            let mut buf = vec![];
+            let mut line_num = 0;
+            let mut before = VecDeque::new();
+            let mut after = 0;
+            let mut last_printed = None;
+            let mut prev_blank = false;
+            loop {
+                let bytes = match file.read_until(b'\n', &mut buf) {
+                    Err(err) => {
+                        eprintln!("{} : {}", filename, err);
+                        failed += 1;
+                        break 'repeat;
+                    }
+                    Ok(bytes) => bytes,
+                };
+                if bytes == 0 {
+                    break;
+                }
+                let raw = if config.show_nonprinting {
+                    Cow::Owned(visible(buf.strip_suffix(b"\n").unwrap_or(&buf)))
+                } else {
+                    String::from_utf8_lossy(&buf)
+                };
+                let line = match config.trim {
+                    Some(trim) => trim_line(chomp(&raw), trim),
+                    None => chomp(&raw),
+                };
+                let expanded;
+                let line = if config.expand_env {
+                    expanded = expand_env(line, config.keep_unset);
+                    expanded.as_str()
+                } else {
+                    line
+                };
+                let masked;
+                let line = match &config.mask {
+                    Some(mask) => {
+                        masked = mask.replace_all(line, NoExpand(&config.mask_with));
+                        masked.as_ref()
+                    }
+                    None => line,
+                };
+                let collapsed;
+                let line = if config.collapse_ws {
+                    collapsed = collapse_whitespace(line);
+                    collapsed.as_str()
+                } else {
+                    line
+                };
+                let squeeze = config.squeeze_blank && prev_blank && line.is_empty();
+                prev_blank = line.is_empty();
+                if squeeze {
+                    offset += bytes;
+                    buf.clear();
+                    continue;
+                }
+                let body = if config.show_tabs {
+                    Cow::Owned(line.replace('\t', "^I"))
+                } else {
+                    Cow::Borrowed(line)
+                };
+                let mut text = if config.byte_offset {
+                    format!("{:6}\t{}", offset, body)
+                } else if config.number_lines || (config.number_nonblank_lines && !line.is_empty())
+                {
+                    let num = next_num.ok_or("line number overflow")?;
+                    next_num = num.checked_add(config.number_step);
+                    format!("{}\t{}", format_number(num, config.number_base), body)
+                } else {
+                    body.into_owned()
+                };
+                if config.show_ends {
+                    text.push('$');
+                }
+                match &config.grep {
+                    None => writeln!(out, "{}", text)?,
+                    Some(pattern) => {
+                        if pattern.is_match(line) {
+                            let first = line_num - before.len();
+                            if config.context > 0
+                                && last_printed.is_some_and(|last| last + 1 < first)
+                            {
+                                writeln!(out, "--")?;
+                            }
+                            for text in before.drain(..) {
+                                writeln!(out, "{}", text)?;
+                            }
+                            writeln!(out, "{}", text)?;
+                            after = config.context;
+                            last_printed = Some(line_num);
+                        } else if after > 0 {
+                            writeln!(out, "{}", text)?;
+                            after -= 1;
+                            last_printed = Some(line_num);
+                        } else if config.context > 0 {
+                            if before.len() == config.context {
+                                before.pop_front();
+                            }
+                            before.push_back(text);
                         }
-                    } else {
-                        println!("{}", line);
                     }
                 }
+                line_num += 1;
+                offset += bytes;
+                buf.clear();
+            }
+        }
+    }
+    Ok(failed)
+}
+
+fn read_all(mut file: impl BufRead) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read the whole input and return its lines last to first, each ending
+/// in a newline. Everything else then sees the reversed text, so line
+/// numbers and byte offsets count from the top of the reversed output.
+fn reverse_lines(file: impl BufRead) -> io::Result<Vec<u8>> {
+    let buf = read_all(file)?;
+    let mut reversed = Vec::with_capacity(buf.len() + 1);
+    for line in buf.split_inclusive(|b| *b == b'\n').rev() {
+        reversed.extend_from_slice(line);
+        if !line.ends_with(b"\n") {
+            reversed.push(b'\n');
+        }
+    }
+    Ok(reversed)
+}
+
+fn chomp(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .unwrap_or(line)
+}
+
+fn format_number(num: usize, base: NumberBase) -> String {
+    match base {
+        NumberBase::Dec => format!("{:6}", num),
+        NumberBase::Hex => format!("0x{:08x}", num),
+        NumberBase::Oct => format!("0o{:08o}", num),
+    }
+}
+
+/// Show control characters in caret notation (e.g. "^A", with DEL as "^?")
+/// and bytes with the high bit set as "M-" followed by the low seven bits.
+/// Tabs are left alone.
+fn visible(line: &[u8]) -> String {
+    let mut res = String::with_capacity(line.len());
+    for &byte in line {
+        let low = if byte >= 128 {
+            res.push_str("M-");
+            byte - 128
+        } else {
+            byte
+        };
+        match low {
+            b'\t' if byte == low => res.push('\t'),
+            0..=31 => {
+                res.push('^');
+                res.push((low + 64) as char);
+            }
+            127 => res.push_str("^?"),
+            _ => res.push(low as char),
+        }
+    }
+    res
+}
+
+/// Replace each `${NAME}` with the value of the environment variable, or
+/// with nothing (or the token itself if `keep_unset`) when it is not set.
+/// A `$` that is not followed by `{`, or a `${` with no closing `}`, is
+/// left alone.
+fn expand_env(line: &str, keep_unset: bool) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let token = &rest[start..start + len + 3];
+        res.push_str(&rest[..start]);
+        match env::var(&token[2..token.len() - 1]) {
+            Ok(val) => res.push_str(&val),
+            Err(_) if keep_unset => res.push_str(token),
+            Err(_) => {}
+        }
+        rest = &rest[start + token.len()..];
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Replace every run of whitespace with a single space
+fn collapse_whitespace(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut in_space = false;
+    for c in line.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                res.push(' ');
             }
+            in_space = true;
+        } else {
+            res.push(c);
+            in_space = false;
         }
     }
-    Ok(())
+    res
+}
+
+fn trim_line(line: &str, trim: Trim) -> &str {
+    match trim {
+        Trim::Left => line.trim_start(),
+        Trim::Right => line.trim_end(),
+        Trim::Both => line.trim(),
+    }
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+        _ => match open_fd(filename) {
+            Some(file) => Ok(Box::new(BufReader::new(file?))),
+            None => Ok(Box::new(BufReader::new(File::open(filename)?))),
+        },
     }
 }
 
+/// Open a "/dev/fd/N" path from a duplicate of descriptor N, so the
+/// original descriptor is left for its owner to close.
+#[cfg(unix)]
+fn open_fd(filename: &str) -> Option<io::Result<File>> {
+    use std::os::unix::io::{BorrowedFd, RawFd};
+
+    let fd: RawFd = filename.strip_prefix("/dev/fd/")?.parse().ok()?;
+    if fd < 0 {
+        return None;
+    }
+    // SAFETY: fcntl only reads the descriptor flags and fails with EBADF
+    // instead of touching anything when `fd` is not open
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Some(Err(io::Error::last_os_error()));
+    }
+    // SAFETY: `fd` was just checked to be open, and nothing on this thread
+    // closes it before the borrow ends with the dup below
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    Some(fd.try_clone_to_owned().map(File::from))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_filename: &str) -> Option<io::Result<File>> {
+    None
+}
+
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
     number_lines: bool,
     number_nonblank_lines: bool,
+    number_from: usize,
+    number_step: usize,
+    number_base: NumberBase,
+    byte_offset: bool,
+    repeat: usize,
+    repeat_reset: bool,
+    grep: Option<Regex>,
+    context: usize,
+    trim: Option<Trim>,
+    squeeze_blank: bool,
+    show_ends: bool,
+    show_tabs: bool,
+    collapse_ws: bool,
+    expand_env: bool,
+    keep_unset: bool,
+    mask: Option<Regex>,
+    mask_with: String,
+    show_nonprinting: bool,
+    reverse: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NumberBase {
+    Dec,
+    Hex,
+    Oct,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Trim {
+    Left,
+    Right,
+    Both,
 }
 
 pub fn get_args() -> MyResult<Config> {
+    parse_args(env::args_os())
+}
+
+fn parse_args<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
     let matches = App::new("catr")
         .version("0.1.0")
         .author("Norihito <norihito@example.com>")
@@ -74,11 +396,364 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Number the non-blank output lines")
                 .takes_value(false),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("squeeze_blank")
+                .short("s")
+                .long("squeeze-blank")
+                .help("Suppress repeated empty output lines")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_all")
+                .short("A")
+                .long("show-all")
+                .help("Equivalent to -vET")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_nonprinting")
+                .short("v")
+                .long("show-nonprinting")
+                .help("Use ^ and M- notation, except for LFD and TAB")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_ends")
+                .short("E")
+                .long("show-ends")
+                .help("Display $ at end of each line")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_tabs")
+                .short("T")
+                .long("show-tabs")
+                .help("Display TAB characters as ^I")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("number_from")
+                .value_name("NUM")
+                .long("number-from")
+                .visible_alias("number-start")
+                .help("First line number")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("number_step")
+                .value_name("NUM")
+                .long("number-step")
+                .help("Line number increment")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("number_base")
+                .value_name("BASE")
+                .long("number-base")
+                .help("Base to print line numbers in")
+                .possible_values(&["dec", "hex", "oct"])
+                .default_value("dec"),
+        )
+        .arg(
+            Arg::with_name("byte_offset")
+                .long("byte-offset")
+                .help("Prefix each line with its byte offset")
+                .takes_value(false)
+                .conflicts_with_all(&["number_lines", "number_nonblank_lines"]),
+        )
+        .arg(
+            Arg::with_name("repeat")
+                .value_name("N")
+                .long("repeat")
+                .help("Print each file N times")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("repeat_reset")
+                .long("repeat-reset")
+                .help("Restart numbering on each repetition")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("grep")
+                .value_name("PATTERN")
+                .long("grep")
+                .help("Only print lines matching PATTERN"),
+        )
+        .arg(
+            Arg::with_name("context")
+                .value_name("NUM")
+                .long("context")
+                .help("Print NUM lines of context around --grep matches")
+                .requires("grep"),
+        )
+        .arg(
+            Arg::with_name("trim")
+                .value_name("SIDE")
+                .long("trim")
+                .help("Strip whitespace from the left, right or both sides of each line")
+                .possible_values(&["left", "right", "both"])
+                .min_values(0)
+                .require_equals(true),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .short("r")
+                .long("reverse")
+                .help("Print the lines of each file last to first")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("collapse_ws")
+                .long("collapse-ws")
+                .help("Squeeze each run of whitespace within a line to one space")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("expand_env")
+                .long("expand-env")
+                .help("Replace ${VAR} with the value of the environment variable VAR")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("keep_unset")
+                .long("keep-unset")
+                .help("With --expand-env, leave ${VAR} as is when VAR is not set")
+                .takes_value(false)
+                .requires("expand_env"),
+        )
+        .arg(
+            Arg::with_name("mask")
+                .value_name("PATTERN")
+                .long("mask")
+                .help("Replace text matching PATTERN with [REDACTED]"),
+        )
+        .arg(
+            Arg::with_name("mask_with")
+                .value_name("STR")
+                .long("mask-with")
+                .help("Text to replace --mask matches with")
+                .requires("mask"),
+        )
+        .arg(
+            Arg::with_name("fd")
+                .value_name("FD")
+                .long("fd")
+                .help("Read from the open file descriptor FD"),
+        )
+        .get_matches_from(args);
+
+    let number_from = matches
+        .value_of("number_from")
+        .map(parse_int)
+        .transpose()
+        .map_err(|e| format!("illegal line number -- {}", e))?;
+    let number_step = matches
+        .value_of("number_step")
+        .map(parse_positive_int)
+        .transpose()
+        .map_err(|e| format!("illegal line number increment -- {}", e))?;
+    let repeat = matches
+        .value_of("repeat")
+        .map(parse_positive_int)
+        .transpose()
+        .map_err(|e| format!("illegal repeat count -- {}", e))?;
+    let grep = matches
+        .value_of("grep")
+        .map(|val| Regex::new(val).map_err(|_| format!("illegal pattern -- {}", val)))
+        .transpose()?;
+    let mask = matches
+        .value_of("mask")
+        .map(|val| Regex::new(val).map_err(|_| format!("illegal pattern -- {}", val)))
+        .transpose()?;
+    let context = matches
+        .value_of("context")
+        .map(parse_int)
+        .transpose()
+        .map_err(|e| format!("illegal context length -- {}", e))?;
+    let show_all = matches.is_present("show_all");
+    let number_base = match matches.value_of("number_base") {
+        Some("hex") => NumberBase::Hex,
+        Some("oct") => NumberBase::Oct,
+        _ => NumberBase::Dec,
+    };
+    let trim = if matches.is_present("trim") {
+        match matches.value_of("trim") {
+            Some("left") => Some(Trim::Left),
+            Some("right") => Some(Trim::Right),
+            _ => Some(Trim::Both),
+        }
+    } else {
+        None
+    };
+
+    let mut files = matches.values_of_lossy("files").unwrap();
+    if let Some(fd) = matches.value_of("fd") {
+        let fd = parse_int(fd).map_err(|e| format!("illegal file descriptor -- {}", e))?;
+        if matches.occurrences_of("files") == 0 {
+            files.clear();
+        }
+        files.push(format!("/dev/fd/{}", fd));
+    }
 
     Ok(Config {
-        files: matches.values_of_lossy("files").unwrap(),
+        files,
         number_lines: matches.is_present("number_lines"),
         number_nonblank_lines: matches.is_present("number_nonblank_lines"),
+        number_from: number_from.unwrap(),
+        number_step: number_step.unwrap(),
+        number_base,
+        byte_offset: matches.is_present("byte_offset"),
+        repeat: repeat.unwrap(),
+        repeat_reset: matches.is_present("repeat_reset"),
+        grep,
+        context: context.unwrap_or(0),
+        trim,
+        squeeze_blank: matches.is_present("squeeze_blank"),
+        show_ends: show_all || matches.is_present("show_ends"),
+        show_tabs: show_all || matches.is_present("show_tabs"),
+        collapse_ws: matches.is_present("collapse_ws"),
+        expand_env: matches.is_present("expand_env"),
+        keep_unset: matches.is_present("keep_unset"),
+        mask,
+        mask_with: matches
+            .value_of("mask_with")
+            .unwrap_or("[REDACTED]")
+            .to_string(),
+        show_nonprinting: show_all || matches.is_present("show_nonprinting"),
+        reverse: matches.is_present("reverse"),
     })
 }
+
+fn parse_int(val: &str) -> MyResult<usize> {
+    val.parse().map_err(|_| From::from(val))
+}
+
+fn parse_positive_int(val: &str) -> MyResult<usize> {
+    match parse_int(val) {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cat_to_string, expand_env, parse_args, visible, Config, NumberBase};
+    use std::fs;
+
+    fn config(files: &[&str]) -> Config {
+        Config {
+            files: files.iter().map(|f| f.to_string()).collect(),
+            number_lines: false,
+            number_nonblank_lines: false,
+            number_from: 1,
+            number_step: 1,
+            number_base: NumberBase::Dec,
+            byte_offset: false,
+            repeat: 1,
+            repeat_reset: false,
+            grep: None,
+            context: 0,
+            trim: None,
+            squeeze_blank: false,
+            show_ends: false,
+            show_tabs: false,
+            collapse_ws: false,
+            expand_env: false,
+            keep_unset: false,
+            mask: None,
+            mask_with: "[REDACTED]".to_string(),
+            show_nonprinting: false,
+            reverse: false,
+        }
+    }
+
+    #[test]
+    fn test_cat_to_string() {
+        let files = [
+            "tests/inputs/fox.txt",
+            "tests/inputs/spiders.txt",
+            "tests/inputs/the-bustle.txt",
+        ];
+        let res = cat_to_string(&config(&files));
+        assert_eq!(
+            res.unwrap(),
+            fs::read_to_string("tests/expected/all.out").unwrap()
+        );
+
+        let res = cat_to_string(&Config {
+            number_lines: true,
+            ..config(&files)
+        });
+        assert_eq!(
+            res.unwrap(),
+            fs::read_to_string("tests/expected/all.n.out").unwrap()
+        );
+
+        let res = cat_to_string(&Config {
+            number_nonblank_lines: true,
+            ..config(&files)
+        });
+        assert_eq!(
+            res.unwrap(),
+            fs::read_to_string("tests/expected/all.b.out").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_show_all() {
+        let config = parse_args(["catr", "-A"]).unwrap();
+        assert!(config.show_nonprinting);
+        assert!(config.show_ends);
+        assert!(config.show_tabs);
+
+        let config = parse_args(["catr", "-E"]).unwrap();
+        assert!(!config.show_nonprinting);
+        assert!(config.show_ends);
+        assert!(!config.show_tabs);
+    }
+
+    #[test]
+    fn test_show_ends() {
+        let path = std::env::temp_dir().join("catr-test-show-ends.txt");
+        fs::write(&path, "one\n\ttwo\n").unwrap();
+        let file = path.to_str().unwrap();
+
+        let res = cat_to_string(&Config {
+            show_ends: true,
+            ..config(&[file])
+        });
+        assert_eq!(res.unwrap(), "one$\n\ttwo$\n");
+
+        let res = cat_to_string(&Config {
+            show_ends: true,
+            number_lines: true,
+            ..config(&[file])
+        });
+        assert_eq!(res.unwrap(), "     1\tone$\n     2\t\ttwo$\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(expand_env("${PATH}", false), path);
+        assert_eq!(expand_env("a ${PATH} b", false), format!("a {} b", path));
+        assert_eq!(expand_env("$PATH ${PATH", false), "$PATH ${PATH");
+        assert_eq!(expand_env("[${CATR_NO_SUCH_VAR}]", false), "[]");
+        assert_eq!(
+            expand_env("[${CATR_NO_SUCH_VAR}]", true),
+            "[${CATR_NO_SUCH_VAR}]"
+        );
+    }
+
+    #[test]
+    fn test_visible() {
+        assert_eq!(visible(b"plain text"), "plain text");
+        assert_eq!(visible(b"\x01\x1b[0m\r"), "^A^[[0m^M");
+        assert_eq!(visible(b"a\tb\x7f"), "a\tb^?");
+        assert_eq!(visible(b"\x00"), "^@");
+        assert_eq!(visible(b"\x89\xe9\xff"), "M-^IM-iM-^?");
+        assert_eq!(visible("é".as_bytes()), "M-CM-)");
+    }
+}
@@ -8,6 +8,7 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
+const TEN: &str = "tests/inputs/ten.txt";
 
 type TestResult = Result<(), Box<dyn Error>>;
 
@@ -46,11 +47,47 @@ fn skips_bad_file() -> TestResult {
     Command::cargo_bin("catr")?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn good_and_bad_files() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("^{} : .* [(]os error 2[)]\ntests/inputs : .*\n$", bad);
+    Command::cargo_bin("catr")?
+        .args([&bad, "tests/inputs", FOX])
+        .assert()
+        .failure()
+        .stdout(fs::read_to_string("tests/expected/fox.txt.out")?)
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn output_interleaves_with_errors() -> TestResult {
+    let bad = gen_bad_file();
+    let path = std::env::temp_dir().join(format!("catr-{}.out", bad));
+    let out = fs::File::create(&path)?;
+    let status = std::process::Command::new(assert_cmd::cargo::cargo_bin("catr"))
+        .args([FOX, &bad, SPIDERS])
+        .stdout(out.try_clone()?)
+        .stderr(out)
+        .status()?;
+    let output = fs::read_to_string(&path)?;
+    fs::remove_file(&path)?;
+    assert!(!status.success());
+    let expected = format!(
+        "^The quick brown fox jumps over the lazy dog.\n{} : .*\nDon't worry, spiders,\n",
+        bad
+    );
+    assert!(predicate::str::is_match(expected)?.eval(&output));
+    Ok(())
+}
+
 fn run(args: &[&str], expected_file: &str) -> TestResult {
     let expected = fs::read_to_string(expected_file)?;
     Command::cargo_bin("catr")?
@@ -186,3 +223,476 @@ fn all_n() -> TestResult {
 fn all_b() -> TestResult {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn number_from_step() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-from", "0", "--number-step", "5"])
+        .write_stdin("a\nb\nc\nd\n")
+        .assert()
+        .success()
+        .stdout("     0\ta\n     5\tb\n    10\tc\n    15\td\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_nonblank_from_step() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-b", "--number-from", "10", "--number-step", "10"])
+        .write_stdin("a\n\nb\n")
+        .assert()
+        .success()
+        .stdout("    10\ta\n\n    20\tb\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_step_large() -> TestResult {
+    let step = usize::MAX.to_string();
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-step", &step])
+        .write_stdin("a\n")
+        .assert()
+        .success()
+        .stdout("     1\ta\n");
+
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-step", &step])
+        .write_stdin("a\nb\n")
+        .assert()
+        .failure()
+        .stdout("     1\ta\n")
+        .stderr("line number overflow\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_number_step_zero() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-step", "0", FOX])
+        .assert()
+        .failure()
+        .stderr("illegal line number increment -- 0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_byte_offset() -> TestResult {
+    let input = fs::read_to_string(BUSTLE)?;
+    let mut offset = 0;
+    let mut expected = String::new();
+    for line in input.split_inclusive('\n') {
+        expected.push_str(&format!("{:6}\t{}", offset, line));
+        offset += line.len();
+    }
+    Command::cargo_bin("catr")?
+        .args(["--byte-offset", BUSTLE])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_byte_offset_and_number() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--byte-offset", "-n", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn run_fd(fd_args: &str, expected_file: &str) -> TestResult {
+    let input = fs::read_to_string(FOX)?;
+    let expected = fs::read_to_string(expected_file)?;
+    let prg = assert_cmd::cargo::cargo_bin("catr");
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} {} 3<&0", prg.display(), fd_args))
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fox_fd() -> TestResult {
+    run_fd("--fd 3", "tests/expected/fox.txt.out")
+}
+
+#[cfg(unix)]
+#[test]
+fn fox_dev_fd_n() -> TestResult {
+    run_fd("-n /dev/fd/3", "tests/expected/fox.txt.n.out")
+}
+
+#[cfg(unix)]
+#[test]
+fn skips_closed_fd() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--fd", "987"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::is_match(
+            "^/dev/fd/987 : .* [(]os error 9[)]\n$",
+        )?);
+    Ok(())
+}
+
+#[test]
+fn dies_bad_fd() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--fd", "foo"])
+        .assert()
+        .failure()
+        .stderr("illegal file descriptor -- foo\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_repeat_n() -> TestResult {
+    let input = fs::read_to_string(SPIDERS)?;
+    let expected: String = input
+        .repeat(3)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:6}\t{}\n", i + 1, line))
+        .collect();
+    Command::cargo_bin("catr")?
+        .args(["-n", "--repeat=3", SPIDERS])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_repeat_reset_n() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/spiders.txt.n.out")?;
+    Command::cargo_bin("catr")?
+        .args(["-n", "--repeat=3", "--repeat-reset", SPIDERS])
+        .assert()
+        .success()
+        .stdout(expected.repeat(3));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_repeat_n() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--repeat", "2"])
+        .write_stdin("a\nb\n")
+        .assert()
+        .success()
+        .stdout("     1\ta\n     2\tb\n     3\ta\n     4\tb\n");
+
+    Command::cargo_bin("catr")?
+        .args(["-n", "--repeat", "2", "--reverse", "-"])
+        .write_stdin("a\nb\n")
+        .assert()
+        .success()
+        .stdout("     1\tb\n     2\ta\n     3\tb\n     4\ta\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_repeat_zero() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--repeat", "0", FOX])
+        .assert()
+        .failure()
+        .stderr("illegal repeat count -- 0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_grep_context_n() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--grep", "^five$", "--context", "1", TEN])
+        .assert()
+        .success()
+        .stdout("     4\tfour\n     5\tfive\n     6\tsix\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_grep_context_separator() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--grep", "two|four|nine", "--context", "1", TEN])
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\nfour\nfive\n--\neight\nnine\nten\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn grep_no_context_no_separator() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--grep", "a"])
+        .write_stdin("a\nx\na\n")
+        .assert()
+        .success()
+        .stdout("a\na\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_grep() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--grep", "(", TEN])
+        .assert()
+        .failure()
+        .stderr("illegal pattern -- (\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_trim(args: &[&str], expected: &'static str) -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(args)
+        .write_stdin("  a b  \n\t\n c\t\n")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+#[test]
+fn trim_default_both() -> TestResult {
+    run_trim(&["--trim"], "a b\n\nc\n")
+}
+
+#[test]
+fn trim_left() -> TestResult {
+    run_trim(&["--trim=left"], "a b  \n\nc\t\n")
+}
+
+#[test]
+fn trim_right() -> TestResult {
+    run_trim(&["--trim=right"], "  a b\n\n c\n")
+}
+
+#[test]
+fn trim_both_n() -> TestResult {
+    run_trim(&["--trim=both", "-n"], "     1\ta b\n     2\t\n     3\tc\n")
+}
+
+#[test]
+fn trim_both_b() -> TestResult {
+    run_trim(&["--trim=both", "-b"], "     1\ta b\n\n     2\tc\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn squeeze_blank_run() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-s", "-n"])
+        .write_stdin("a\n\n\n\nb\n")
+        .assert()
+        .success()
+        .stdout("     1\ta\n     2\t\n     3\tb\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn squeeze_blank_trailing() -> TestResult {
+    Command::cargo_bin("catr")?
+        .arg("--squeeze-blank")
+        .write_stdin("a\nb\n\n\n\n")
+        .assert()
+        .success()
+        .stdout("a\nb\n\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_base_hex() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-base=hex", "--number-from", "9"])
+        .write_stdin("a\nb\nc\n")
+        .assert()
+        .success()
+        .stdout("0x00000009\ta\n0x0000000a\tb\n0x0000000b\tc\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_ends() -> TestResult {
+    Command::cargo_bin("catr")?
+        .arg("-E")
+        .write_stdin("a b\n\n")
+        .assert()
+        .success()
+        .stdout("a b$\n$\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_ends_numbered() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--show-ends"])
+        .write_stdin("a b\n\n")
+        .assert()
+        .success()
+        .stdout("     1\ta b$\n     2\t$\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_tabs_numbered() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "-T", "-E"])
+        .write_stdin("a\tb\t\n\tc\n")
+        .assert()
+        .success()
+        .stdout("     1\ta^Ib^I$\n     2\t^Ic$\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_all() -> TestResult {
+    for args in [&["-A"][..], &["--show-all", "-E", "-T"]] {
+        Command::cargo_bin("catr")?
+            .args(args)
+            .write_stdin("a\tb\n\n")
+            .assert()
+            .success()
+            .stdout("a^Ib$\n$\n");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn collapse_ws() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["--collapse-ws", "-E"])
+        .write_stdin("a  \t b\t\tc\n\n d \n")
+        .assert()
+        .success()
+        .stdout("a b c$\n$\n d $\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_nonprinting() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-v", "-n"])
+        .write_stdin(&b"bell\x07\r\n\xff\tend\n"[..])
+        .assert()
+        .success()
+        .stdout("     1\tbell^G^M\n     2\tM-^?\tend\n");
+
+    Command::cargo_bin("catr")?
+        .arg("-A")
+        .write_stdin(&b"\x01\t\n"[..])
+        .assert()
+        .success()
+        .stdout("^A^I$\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn reverse() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-r", "-n"])
+        .write_stdin("one\ntwo\nthree")
+        .assert()
+        .success()
+        .stdout("     1\tthree\n     2\ttwo\n     3\tone\n");
+
+    Command::cargo_bin("catr")?
+        .args(["--reverse", EMPTY])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_start() -> TestResult {
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-start", "100"])
+        .write_stdin("a\nb\nc\n")
+        .assert()
+        .success()
+        .stdout("   100\ta\n   101\tb\n   102\tc\n");
+
+    Command::cargo_bin("catr")?
+        .args(["-n", "--number-start=-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line number"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn expand_env() -> TestResult {
+    Command::cargo_bin("catr")?
+        .arg("--expand-env")
+        .env("CATR_GREETING", "hello")
+        .env_remove("CATR_UNSET")
+        .write_stdin("${CATR_GREETING}, $USER\n[${CATR_UNSET}]\n")
+        .assert()
+        .success()
+        .stdout("hello, $USER\n[]\n");
+
+    Command::cargo_bin("catr")?
+        .args(["--expand-env", "--keep-unset"])
+        .env_remove("CATR_UNSET")
+        .write_stdin("[${CATR_UNSET}]\n")
+        .assert()
+        .success()
+        .stdout("[${CATR_UNSET}]\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mask() -> TestResult {
+    let input = "first line\nmail bob@example.com or amy@example.org\n";
+    Command::cargo_bin("catr")?
+        .args(["-n", r"--mask=\S+@\S+"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("     1\tfirst line\n     2\tmail [REDACTED] or [REDACTED]\n");
+
+    Command::cargo_bin("catr")?
+        .args([r"--mask=\S+@\S+", "--mask-with=$1<email>"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("first line\nmail $1<email> or $1<email>\n");
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use crate::Column::*;
 use clap::{App, Arg};
-use std::cmp::Ordering::*;
+use std::cmp::Ordering::{self, *};
+use std::collections::{hash_map::DefaultHasher, HashSet};
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::{
     error::Error,
     io::{self, BufRead, BufReader},
@@ -9,21 +11,30 @@ use std::{
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+const SORT_WARN_BYTES: usize = 100 * 1024 * 1024;
+
 #[derive(Debug)]
 pub struct Config {
     file1: String,
     file2: String,
+    pairs: Option<String>,
     show_col1: bool,
     show_col2: bool,
     show_col3: bool,
     insensitive: bool,
     delimiter: String,
+    summary_json: bool,
+    line_numbers: bool,
+    sort: bool,
+    estimate: bool,
+    sample_rate: f64,
+    seed: u64,
 }
 
 enum Column<'a> {
-    Col1(&'a str),
-    Col2(&'a str),
-    Col3(&'a str),
+    Col1(&'a str, usize),
+    Col2(&'a str, usize),
+    Col3(&'a str, usize, usize),
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -35,13 +46,13 @@ pub fn get_args() -> MyResult<Config> {
             Arg::with_name("file1")
                 .value_name("FILE1")
                 .help("input file1")
-                .required(true),
+                .required_unless("pairs"),
         )
         .arg(
             Arg::with_name("file2")
                 .value_name("FILE2")
                 .help("input file2")
-                .required(true),
+                .required_unless("pairs"),
         )
         .arg(
             Arg::with_name("show_col1")
@@ -64,6 +75,13 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Suppress printing of column 3")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("diff_only_1")
+                .long("diff-only-1")
+                .help("Print only the lines unique to FILE1 (same as -23)")
+                .takes_value(false)
+                .conflicts_with("show_col1"),
+        )
         .arg(
             Arg::with_name("insensitive")
                 .value_name("insensitive")
@@ -80,32 +98,136 @@ pub fn get_args() -> MyResult<Config> {
                 .help("use DELIM instead of TAB for delimiter")
                 .default_value("\t"),
         )
+        .arg(
+            Arg::with_name("summary_json")
+                .long("summary-json")
+                .help("Print the column counts as JSON instead of the lines")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("line_numbers")
+                .long("line-numbers")
+                .help("Prefix each line with its line number in the input file(s)")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .help("Sort each input file in memory before comparing")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("estimate")
+                .long("estimate")
+                .help("Estimate the Jaccard similarity of the two files from a sample of lines")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sample_rate")
+                .value_name("RATE")
+                .long("sample-rate")
+                .help("Fraction of lines to sample with --estimate")
+                .default_value("0.1"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .value_name("SEED")
+                .long("seed")
+                .help("Seed for sampling with --estimate")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("pairs")
+                .value_name("FILE")
+                .long("pairs")
+                .help("Compare each FILE1<TAB>FILE2 pair listed in FILE")
+                .conflicts_with_all(&["file1", "file2"]),
+        )
         .get_matches();
 
+    let sample_rate = parse_rate(matches.value_of("sample_rate").unwrap())?;
+    let seed = parse_u64(matches.value_of("seed").unwrap())?;
+    let diff_only_1 = matches.is_present("diff_only_1");
+
     Ok(Config {
-        file1: matches.value_of("file1").unwrap().to_string(),
-        file2: matches.value_of("file2").unwrap().to_string(),
+        file1: matches.value_of("file1").unwrap_or_default().to_string(),
+        file2: matches.value_of("file2").unwrap_or_default().to_string(),
+        pairs: matches.value_of("pairs").map(String::from),
         show_col1: !matches.is_present("show_col1"),
-        show_col2: !matches.is_present("show_col2"),
-        show_col3: !matches.is_present("show_col3"),
+        show_col2: !(diff_only_1 || matches.is_present("show_col2")),
+        show_col3: !(diff_only_1 || matches.is_present("show_col3")),
         insensitive: matches.is_present("insensitive"),
         delimiter: matches.value_of("delimiter").unwrap().to_string(),
+        summary_json: matches.is_present("summary_json"),
+        line_numbers: matches.is_present("line_numbers"),
+        sort: matches.is_present("sort"),
+        estimate: matches.is_present("estimate"),
+        sample_rate,
+        seed,
     })
 }
 
+fn parse_u64(val: &str) -> MyResult<u64> {
+    val.parse()
+        .map_err(|_| format!("\"{}\" not a valid integer", val).into())
+}
+
+fn parse_rate(val: &str) -> MyResult<f64> {
+    match val.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(rate),
+        _ => Err(format!("sample rate \"{}\" not in the range (0, 1]", val).into()),
+    }
+}
+
 pub fn run(config: Config) -> MyResult<()> {
-    let file1 = &config.file1;
-    let file2 = &config.file2;
+    match &config.pairs {
+        Some(pairs) => {
+            for (i, line) in open(pairs)?.lines().enumerate() {
+                let line = line?;
+                let (file1, file2) = line.split_once('\t').ok_or_else(|| {
+                    format!("{}: line {}: expected FILE1<TAB>FILE2", pairs, i + 1)
+                })?;
+                println!("=== {} vs {} ===", file1, file2);
+                compare(&config, file1, file2)?;
+            }
+            Ok(())
+        }
+        None => compare(&config, &config.file1, &config.file2),
+    }
+}
+
+fn compare(config: &Config, file1: &str, file2: &str) -> MyResult<()> {
+    let (mut only1, mut only2, mut common) = (0, 0, 0);
+    let mut print = |col: Column| {
+        match col {
+            Col1(..) => only1 += 1,
+            Col2(..) => only2 += 1,
+            Col3(..) => common += 1,
+        }
+        if config.summary_json {
+            return;
+        }
+
+        let (val, num) = match col {
+            Col1(val, num1) => (val, num1.to_string()),
+            Col2(val, num2) => (val, num2.to_string()),
+            Col3(val, num1, num2) => (val, format!("{},{}", num1, num2)),
+        };
+        let text = if config.line_numbers {
+            format!("{}:{}", num, val)
+        } else {
+            val.to_string()
+        };
+        let val = text.as_str();
 
-    let print = |col: Column| {
         let mut columns = vec![];
         match col {
-            Col1(val) => {
+            Col1(..) => {
                 if config.show_col1 {
                     columns.push(val);
                 }
             }
-            Col2(val) => {
+            Col2(..) => {
                 if config.show_col2 {
                     if config.show_col1 {
                         columns.push("");
@@ -113,7 +235,7 @@ pub fn run(config: Config) -> MyResult<()> {
                     columns.push(val);
                 }
             }
-            Col3(val) => {
+            Col3(..) => {
                 if config.show_col3 {
                     if config.show_col1 {
                         columns.push("");
@@ -135,26 +257,37 @@ pub fn run(config: Config) -> MyResult<()> {
         return Err(From::from("Both input files cannot be STDIN (\"-\")"));
     }
 
-    let mut lines1 = open(file1)?.lines().filter_map(Result::ok);
-    let mut lines2 = open(file2)?.lines().filter_map(Result::ok);
+    let mut lines1 = read_lines(file1, config)?;
+    let mut lines2 = read_lines(file2, config)?;
+
+    if config.estimate {
+        let similarity = estimate_similarity(
+            lines1.map(|(line, _)| line),
+            lines2.map(|(line, _)| line),
+            config.sample_rate,
+            config.seed,
+        );
+        println!("{:.4}", similarity);
+        return Ok(());
+    }
 
     let mut line1 = lines1.next();
     let mut line2 = lines2.next();
 
     while line1.is_some() || line2.is_some() {
         match (&line1, &line2) {
-            (Some(val1), Some(val2)) => match val1.cmp(val2) {
+            (Some((val1, num1)), Some((val2, num2))) => match compare_lines(val1, val2, config) {
                 Equal => {
-                    print(Col3(val1));
+                    print(Col3(val1, *num1, *num2));
                     line1 = lines1.next();
                     line2 = lines2.next();
                 }
                 Less => {
-                    print(Col1(val1));
+                    print(Col1(val1, *num1));
                     line1 = lines1.next();
                 }
                 Greater => {
-                    print(Col2(val2));
+                    print(Col2(val2, *num2));
                     line2 = lines2.next();
                 } // let _val1 = val1.parse::<char>().unwrap() as u8;
                   // let _val2 = val2.parse::<char>().unwrap() as u8;
@@ -170,21 +303,96 @@ pub fn run(config: Config) -> MyResult<()> {
                   //     line2 = lines2.next();
                   // }
             },
-            (Some(val1), None) => {
-                print(Col1(val1));
+            (Some((val1, num1)), None) => {
+                print(Col1(val1, *num1));
                 line1 = lines1.next();
             }
-            (None, Some(val2)) => {
-                print(Col2(val2));
+            (None, Some((val2, num2))) => {
+                print(Col2(val2, *num2));
                 line2 = lines2.next();
             }
             _ => (),
         }
     }
 
+    if config.summary_json {
+        println!(
+            "{{\"only1\": {}, \"only2\": {}, \"common\": {}}}",
+            only1, only2, common
+        );
+    }
+
     Ok(())
 }
 
+/// Order two lines the way `read_lines` sorts them, ignoring case with
+/// `insensitive`
+fn compare_lines(val1: &str, val2: &str, config: &Config) -> Ordering {
+    if config.insensitive {
+        val1.to_lowercase().cmp(&val2.to_lowercase())
+    } else {
+        val1.cmp(val2)
+    }
+}
+
+/// Approximate the Jaccard similarity of the two line sets.
+/// Lines are kept when their seeded hash falls under `rate`, so a line
+/// is sampled from both files or from neither.
+fn estimate_similarity(
+    lines1: impl Iterator<Item = String>,
+    lines2: impl Iterator<Item = String>,
+    rate: f64,
+    seed: u64,
+) -> f64 {
+    let set1 = sample_hashes(lines1, rate, seed);
+    let set2 = sample_hashes(lines2, rate, seed);
+
+    let union = set1.union(&set2).count();
+    if union == 0 {
+        return 1.0;
+    }
+    set1.intersection(&set2).count() as f64 / union as f64
+}
+
+fn sample_hashes(lines: impl Iterator<Item = String>, rate: f64, seed: u64) -> HashSet<u64> {
+    lines
+        .map(|line| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            line.hash(&mut hasher);
+            hasher.finish()
+        })
+        .filter(|hash| (*hash as f64 / u64::MAX as f64) < rate)
+        .collect()
+}
+
+/// The lines of a file paired with their line numbers, which are kept
+/// through `sort`
+fn read_lines(
+    filename: &str,
+    config: &Config,
+) -> MyResult<Box<dyn Iterator<Item = (String, usize)>>> {
+    let lines = open(filename)?.lines().filter_map(Result::ok).zip(1..);
+    if !config.sort {
+        return Ok(Box::new(lines));
+    }
+
+    let mut lines: Vec<_> = lines.collect();
+    let size: usize = lines.iter().map(|(line, _)| line.len()).sum();
+    if size > SORT_WARN_BYTES {
+        eprintln!(
+            "commr: warning: sorting {} in memory ({} bytes)",
+            filename, size
+        );
+    }
+    if config.insensitive {
+        lines.sort_by_key(|(line, _)| line.to_lowercase());
+    } else {
+        lines.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    Ok(Box::new(lines.into_iter()))
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -193,3 +401,34 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::estimate_similarity;
+
+    fn lines(range: std::ops::Range<u32>) -> impl Iterator<Item = String> {
+        range.map(|n| format!("line {}", n))
+    }
+
+    #[test]
+    fn test_estimate_similarity_exact() {
+        // Sampling every line gives the exact similarity
+        let res = estimate_similarity(lines(0..10), lines(5..15), 1.0, 0);
+        assert!((res - 5.0 / 15.0).abs() < 1e-9);
+
+        assert_eq!(estimate_similarity(lines(0..10), lines(0..10), 1.0, 0), 1.0);
+        assert_eq!(
+            estimate_similarity(lines(0..10), lines(10..20), 1.0, 0),
+            0.0
+        );
+        assert_eq!(estimate_similarity(lines(0..0), lines(0..0), 1.0, 0), 1.0);
+    }
+
+    #[test]
+    fn test_estimate_similarity_sampled() {
+        for seed in 0..3 {
+            let res = estimate_similarity(lines(0..10000), lines(5000..15000), 0.1, seed);
+            assert!((res - 1.0 / 3.0).abs() < 0.05, "seed {} gave {}", seed, res);
+        }
+    }
+}
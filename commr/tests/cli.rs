@@ -341,3 +341,132 @@ fn blank_file1() -> TestResult {
 //fn file1_blanks() -> TestResult {
 //    run(&[FILE1, BLANKS], "tests/expected/file1_blanks.out")
 //}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_summary_json() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "--summary-json"])
+        .assert()
+        .success()
+        .stdout("{\"only1\": 3, \"only2\": 1, \"common\": 1}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_estimate() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "--estimate", "--sample-rate", "1"])
+        .assert()
+        .success()
+        .stdout("0.2000\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sample_rate() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "--estimate", "--sample-rate", "0"])
+        .assert()
+        .failure()
+        .stderr("sample rate \"0\" not in the range (0, 1]\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_line_numbers() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--line-numbers", FILE1, FILE2])
+        .assert()
+        .success()
+        .stdout("\t1:B\n1:a\n2:b\n\t\t3,2:c\n4:d\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pairs() -> TestResult {
+    let expected = format!(
+        "=== {} vs {} ===\n\tB\na\nb\n\t\tc\nd\n=== {} vs {} ===\n\ta\n\tb\n\tc\n\td\n",
+        FILE1, FILE2, EMPTY, FILE1
+    );
+    Command::cargo_bin(PRG)?
+        .args(["--pairs", "tests/inputs/pairs.txt"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_pairs_line() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--pairs", "-"])
+        .write_stdin(format!("{} {}\n", FILE1, FILE2))
+        .assert()
+        .failure()
+        .stderr("-: line 1: expected FILE1<TAB>FILE2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_sort() -> TestResult {
+    run(
+        &[
+            "--sort",
+            "tests/inputs/unsorted1.txt",
+            "tests/inputs/unsorted2.txt",
+        ],
+        "tests/expected/file1_file2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_sort_line_numbers() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "--sort",
+            "--line-numbers",
+            "tests/inputs/unsorted1.txt",
+            "tests/inputs/unsorted2.txt",
+        ])
+        .assert()
+        .success()
+        .stdout("\t2:B\n2:a\n4:b\n\t\t3,1:c\n1:d\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_sort_i() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "--sort",
+            "-i",
+            "tests/inputs/unsorted1.txt",
+            "tests/inputs/unsorted2.txt",
+        ])
+        .assert()
+        .success()
+        .stdout("a\n\t\tb\n\t\tc\nd\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_diff_only_1() -> TestResult {
+    run(
+        &["--diff-only-1", FILE1, FILE2],
+        "tests/expected/file1_file2.23.out",
+    )?;
+    run(
+        &["--diff-only-1", "-d", ":", FILE1, FILE2],
+        "tests/expected/file1_file2.23.out",
+    )
+}
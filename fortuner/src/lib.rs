@@ -5,18 +5,31 @@ use std::fs::metadata;
 use std::{
     error::Error,
     fs,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::PathBuf,
 };
 use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// Same reading speed and minimum wait as BSD fortune's -w
+const CHARS_PER_SEC: usize = 20;
+const MIN_WAIT_SECS: usize = 6;
+
 #[derive(Debug)]
 pub struct Config {
     sources: Vec<String>,
+    only_sources: Vec<String>,
     pattern: Option<Regex>,
+    match_field: Option<String>,
+    exclude: Option<Regex>,
     seed: Option<u64>,
+    dry_run: bool,
+    wait_preview: bool,
+    round_robin: bool,
+    max_scan: Option<usize>,
+    match_summary: bool,
+    to: Option<String>,
 }
 
 #[derive(Debug)]
@@ -37,6 +50,14 @@ pub fn get_args() -> MyResult<Config> {
                 .multiple(true)
                 .required(true),
         )
+        .arg(
+            Arg::with_name("only_source")
+                .value_name("NAME")
+                .help("Only use sources with this file name")
+                .long("only-source")
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             Arg::with_name("pattern")
                 .value_name("PATTERN")
@@ -59,54 +80,221 @@ pub fn get_args() -> MyResult<Config> {
                 .long("insensitive")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("match_field")
+                .value_name("PREFIX")
+                .help("Only match lines starting with PREFIX")
+                .long("match-field")
+                .requires("pattern"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .value_name("EXCLUDE")
+                .help("Skip fortunes matching this pattern")
+                .long("exclude"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .help("Report the fortune that would be picked")
+                .long("dry-run")
+                .takes_value(false)
+                .conflicts_with_all(&["pattern", "exclude"]),
+        )
+        .arg(
+            Arg::with_name("wait_preview")
+                .help("Print how long the fortune would be shown for")
+                .long("wait-preview")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("round_robin")
+                .help("Print one fortune from each source")
+                .long("round-robin")
+                .takes_value(false)
+                .conflicts_with_all(&["pattern", "exclude", "dry_run"]),
+        )
+        .arg(
+            Arg::with_name("max_scan")
+                .value_name("N")
+                .help("Give up after examining N fortunes for a match")
+                .long("max-scan"),
+        )
+        .arg(
+            Arg::with_name("match_summary")
+                .help("After searching, print how many fortunes matched in each source")
+                .long("match-summary")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("to")
+                .value_name("FILE")
+                .help("Write the matching fortunes to FILE as a cookie file")
+                .long("to")
+                .visible_alias("concat-output")
+                .requires("pattern"),
+        )
         .get_matches();
 
+    let insensitive = matches.is_present("insensitive");
     let pattern = matches
         .value_of("pattern")
-        .map(|val| {
-            RegexBuilder::new(val)
-                .case_insensitive(matches.is_present("insensitive"))
-                .build()
-                .map_err(|_| format!("Invalid --pattern {}", val))
-        })
+        .map(|val| build_regex(val, insensitive).map_err(|_| format!("Invalid --pattern {}", val)))
+        .transpose()?;
+    let exclude = matches
+        .value_of("exclude")
+        .map(|val| build_regex(val, insensitive).map_err(|_| format!("Invalid --exclude {}", val)))
         .transpose()?;
 
     let seed = matches.value_of("seed").map(parse_u64).transpose()?;
+    let max_scan = matches
+        .value_of("max_scan")
+        .map(parse_u64)
+        .transpose()?
+        .map(|n| n as usize);
 
     Ok(Config {
         sources: matches.values_of_lossy("sources").unwrap(),
+        only_sources: matches.values_of_lossy("only_source").unwrap_or_default(),
         pattern,
+        match_field: matches.value_of("match_field").map(String::from),
+        exclude,
         seed,
+        dry_run: matches.is_present("dry_run"),
+        wait_preview: matches.is_present("wait_preview"),
+        round_robin: matches.is_present("round_robin"),
+        max_scan,
+        match_summary: matches.is_present("match_summary"),
+        to: matches.value_of("to").map(String::from),
     })
 }
 
+fn build_regex(val: &str, insensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(val).case_insensitive(insensitive).build()
+}
+
 fn parse_u64(val: &str) -> MyResult<u64> {
     val.parse()
         .map_err(|_| format!("\"{}\" not a valid integer", val).into())
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    let files = find_files(&config.sources)?;
+    let mut files = find_files(&config.sources)?;
+    if !config.only_sources.is_empty() {
+        files.retain(|file| {
+            file.file_name()
+                .is_some_and(|name| config.only_sources.iter().any(|s| name == s.as_str()))
+        });
+    }
     let fortunes = read_fortunes(&files)?;
 
-    if let Some(pattern) = config.pattern {
+    if config.pattern.is_some() || config.exclude.is_some() {
+        let mut out: Box<dyn Write> = match &config.to {
+            Some(path) => Box::new(BufWriter::new(
+                fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+            )),
+            None => Box::new(io::stdout()),
+        };
         let mut prev_source = None;
-        for fortune in fortunes.iter().filter(|f| pattern.is_match(&f.text)) {
+        let mut counts: Vec<(&str, usize)> = vec![];
+        let limit = config.max_scan.unwrap_or(usize::MAX).min(fortunes.len());
+        let scanned = &fortunes[..limit];
+        for fortune in scanned.iter().filter(|f| is_selected(&config, &f.text)) {
             if prev_source.as_ref().map_or(true, |s| s != &fortune.source) {
                 eprintln!("({})\n%", fortune.source);
                 prev_source = Some(fortune.source.clone());
             }
-            println!("{}\n%", fortune.text);
+            write_fortune(&mut out, &fortune.text)?;
+            match counts
+                .iter_mut()
+                .find(|(source, _)| *source == fortune.source)
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((&fortune.source, 1)),
+            }
+        }
+        out.flush()?;
+        if prev_source.is_none() && scanned.len() < fortunes.len() {
+            eprintln!("No match in the first {} fortunes", scanned.len());
+        }
+        if config.match_summary {
+            for (source, count) in counts {
+                eprintln!("{}: {}", source, count);
+            }
+        }
+    } else if config.round_robin {
+        let texts: Vec<_> = pick_round_robin(&fortunes, config.seed)
+            .iter()
+            .map(|f| f.text.as_str())
+            .collect();
+        if texts.is_empty() {
+            println!("No fortunes found");
+        } else {
+            println!("{}", texts.join("\n%\n"));
+        }
+    } else if config.dry_run {
+        match choose_fortune(&fortunes, config.seed) {
+            Some(fortune) => {
+                let index = fortunes
+                    .iter()
+                    .filter(|f| f.source == fortune.source)
+                    .position(|f| std::ptr::eq(f, fortune))
+                    .unwrap();
+                eprintln!(
+                    "source: {}\nindex: {}\nlength: {}",
+                    fortune.source,
+                    index,
+                    fortune.text.chars().count()
+                );
+            }
+            None => eprintln!("No fortunes found"),
         }
     } else {
-        println!(
-            "{}",
-            pick_fortune(&fortunes, config.seed).unwrap_or_else(|| "No fortunes found".to_string())
-        )
+        match pick_fortune(&fortunes, config.seed) {
+            Some(text) => {
+                println!("{}", text);
+                if config.wait_preview {
+                    println!("wait: {}s", wait_secs(&text));
+                }
+            }
+            None => println!("No fortunes found"),
+        }
     }
     Ok(())
 }
 
+/// Write a fortune in the `%`-terminated form `read_fortunes` reads
+fn write_fortune(out: &mut impl Write, text: &str) -> io::Result<()> {
+    writeln!(out, "{}\n%", text)
+}
+
+fn wait_secs(text: &str) -> usize {
+    (text.chars().count() / CHARS_PER_SEC).max(MIN_WAIT_SECS)
+}
+
+fn is_selected(config: &Config, text: &str) -> bool {
+    if let Some(pattern) = &config.pattern {
+        if !is_match(pattern, config.match_field.as_deref(), text) {
+            return false;
+        }
+    }
+    if let Some(exclude) = &config.exclude {
+        if exclude.is_match(text) {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_match(pattern: &Regex, field: Option<&str>, text: &str) -> bool {
+    match field {
+        Some(prefix) => text
+            .lines()
+            .filter(|line| line.starts_with(prefix))
+            .any(|line| pattern.is_match(line)),
+        None => pattern.is_match(text),
+    }
+}
+
 fn find_files(paths: &[String]) -> MyResult<Vec<PathBuf>> {
     let mut result = vec![];
     for path in paths {
@@ -157,19 +345,38 @@ fn read_fortunes(paths: &[PathBuf]) -> MyResult<Vec<Fortune>> {
 }
 
 fn pick_fortune(fortunes: &[Fortune], seed: Option<u64>) -> Option<String> {
+    choose_fortune(fortunes, seed).map(|f| f.text.to_string())
+}
+
+fn choose_fortune(fortunes: &[Fortune], seed: Option<u64>) -> Option<&Fortune> {
     if let Some(val) = seed {
         let mut rng = StdRng::seed_from_u64(val);
-        fortunes.choose(&mut rng).map(|f| f.text.to_string())
+        fortunes.choose(&mut rng)
     } else {
         let mut rng = rand::thread_rng();
-        fortunes.choose(&mut rng).map(|f| f.text.to_string())
+        fortunes.choose(&mut rng)
     }
 }
 
+/// Choose one fortune from each source, in source order
+fn pick_round_robin(fortunes: &[Fortune], seed: Option<u64>) -> Vec<&Fortune> {
+    let mut rng = match seed {
+        Some(val) => StdRng::seed_from_u64(val),
+        None => StdRng::from_entropy(),
+    };
+    fortunes
+        .chunk_by(|a, b| a.source == b.source)
+        .filter_map(|group| group.choose(&mut rng))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{find_files, pick_fortune, read_fortunes, Fortune};
-    use std::path::PathBuf;
+    use super::{
+        find_files, is_match, pick_fortune, read_fortunes, wait_secs, write_fortune, Fortune,
+    };
+    use regex::Regex;
+    use std::{env, fs, path::PathBuf};
 
     #[test]
     fn test_find_files() {
@@ -247,6 +454,25 @@ mod tests {
         assert_eq!(res.unwrap().len(), 11);
     }
 
+    #[test]
+    fn test_write_fortune() {
+        let fortunes = read_fortunes(&[PathBuf::from("./tests/inputs/jokes")]).unwrap();
+        let mut out = vec![];
+        for fortune in &fortunes {
+            write_fortune(&mut out, &fortune.text).unwrap();
+        }
+        assert!(out.ends_with(b"\n%\n"));
+
+        // The written cookie file reads back as the same fortunes
+        let paths = [env::temp_dir().join(format!("fortuner-test-{}", std::process::id()))];
+        fs::write(&paths[0], out).unwrap();
+        let res = read_fortunes(&paths);
+        fs::remove_file(&paths[0]).unwrap();
+        let texts: Vec<_> = res.unwrap().into_iter().map(|f| f.text).collect();
+        let expected: Vec<_> = fortunes.into_iter().map(|f| f.text).collect();
+        assert_eq!(texts, expected);
+    }
+
     #[test]
     fn test_pick_fortune() {
         // Create a slice of fortunes
@@ -273,4 +499,28 @@ mod tests {
             "Neckties strangle clear thinking.".to_string()
         );
     }
+
+    #[test]
+    fn test_is_match() {
+        let re = Regex::new("toad").unwrap();
+        let text = "Q: What happens when frogs park illegally?\nA: They get toad.";
+        assert!(is_match(&re, None, text));
+        assert!(is_match(&re, Some("A:"), text));
+        assert!(!is_match(&re, Some("Q:"), text));
+
+        let re = Regex::new("frogs").unwrap();
+        assert!(!is_match(&re, Some("A:"), text));
+    }
+
+    #[test]
+    fn test_wait_secs() {
+        // Short fortunes wait for the minimum time
+        assert_eq!(wait_secs(""), 6);
+        assert_eq!(wait_secs("Neckties strangle clear thinking."), 6);
+        assert_eq!(wait_secs(&"x".repeat(139)), 6);
+
+        // Longer fortunes get a second per 20 characters
+        assert_eq!(wait_secs(&"x".repeat(140)), 7);
+        assert_eq!(wait_secs(&"x".repeat(1000)), 50);
+    }
 }
@@ -173,3 +173,157 @@ fn mark_twain_lower_i() -> TestResult {
         "tests/expected/twain_lower_i.err",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn jokes_match_field_answer() -> TestResult {
+    run(
+        &[JOKES, "-m", "toad", "--match-field", "A:"],
+        "Q: What happens when frogs park illegally?\nA: They get toad.\n%\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jokes_match_field_skips_question() -> TestResult {
+    run(&[JOKES, "-m", "frogs", "--match-field", "A:"], "")
+}
+
+// --------------------------------------------------
+#[test]
+fn quotes_exclude_only() -> TestResult {
+    run(
+        &[QUOTES, "--exclude", "Twain"],
+        "It's like deja vu all over again.\n-- Yogi Berra\n%\n\
+        You can observe a lot just by watching.\n-- Yogi Berra\n%\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quotes_include_exclude_insensitive() -> TestResult {
+    run(
+        &[QUOTES, "-i", "-m", "yogi", "--exclude", "DEJA"],
+        "You can observe a lot just by watching.\n-- Yogi Berra\n%\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quotes_include_and_exclude_match() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([QUOTES, "-m", "Twain", "--exclude", "compliment"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout.matches("-- Mark Twain").count(), 2);
+    assert!(!stdout.contains("compliment"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jokes_dry_run_seed_1() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([JOKES, "-s", "1", "--dry-run"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("source: jokes\nindex: 4\nlength: 60\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jokes_wait_preview_seed_1() -> TestResult {
+    run(
+        &[JOKES, "-s", "1", "--wait-preview"],
+        "Q: What happens when frogs park illegally?\nA: They get toad.\nwait: 6s\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dir_only_source_jokes() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([FORTUNE_DIR, "--only-source", "jokes", "-m", "."])
+        .assert()
+        .success()
+        .stderr("(jokes)\n%\n");
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout.matches("\n%\n").count(), 6);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jokes_quotes_round_robin() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([JOKES, QUOTES, "--round-robin", "-s", "1"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let picked: Vec<_> = stdout.trim_end().split("\n%\n").collect();
+    assert_eq!(picked.len(), 2);
+    assert!(fs::read_to_string(JOKES)?.contains(picked[0]));
+    assert!(fs::read_to_string(QUOTES)?.contains(picked[1]));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jokes_max_scan() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([JOKES, "-m", "deer", "--max-scan", "5"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("No match in the first 5 fortunes\n");
+
+    Command::cargo_bin(PRG)?
+        .args([JOKES, "-m", "deer", "--max-scan", "6"])
+        .assert()
+        .success()
+        .stderr("(jokes)\n%\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir_match_summary() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FORTUNE_DIR, "-m", "the", "--match-summary"])
+        .assert()
+        .success()
+        .stderr(predicate::str::ends_with(
+            "jokes: 3\nliterature: 1\nquotes: 2\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quotes_to_file() -> TestResult {
+    let expected = Command::cargo_bin(PRG)?
+        .args([QUOTES, "-m", "the"])
+        .output()?
+        .stdout;
+
+    let out_file = std::env::temp_dir().join(format!("fortuner-{}", random_string()));
+    let out_path = out_file.to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([QUOTES, "-m", "the", "--to", out_path])
+        .assert()
+        .success()
+        .stdout("");
+    let written = fs::read(&out_file)?;
+
+    // The cookie file can itself be searched
+    let reread = Command::cargo_bin(PRG)?
+        .args([out_path, "-m", "."])
+        .output()?;
+    fs::remove_file(&out_file)?;
+    assert_eq!(written, expected);
+    assert_eq!(reread.stdout, expected);
+    Ok(())
+}
@@ -3,19 +3,79 @@ mod owner;
 use chrono::{DateTime, Local};
 use clap::{App, Arg};
 use users::{get_user_by_uid, get_group_by_gid};
-use std::{error::Error, path::PathBuf, fs::{metadata, read_dir}, os::unix::prelude::MetadataExt};
+use sha2::{Digest, Sha256};
+use std::{cmp::Reverse, env, error::Error, io, path::{Path, PathBuf}, fs::{canonicalize, metadata, read_dir, read_link, File, Metadata}, os::unix::prelude::MetadataExt};
 use tabular::{Row, Table};
 use owner::Owner;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Config{
     paths: Vec<String>,
     long: bool,
     show_hidden: bool,
+    strict: bool,
+    count_only: bool,
+    size_format: SizeFormat,
+    one_file_system: bool,
+    dereference_size: bool,
+    max_entries: Option<usize>,
+    min_inode: Option<u64>,
+    relative_time: bool,
+    checksum: Option<Checksum>,
+    full_path: bool,
+    no_group: bool,
+    icons: bool,
+    reverse: bool,
+    sort_time: bool,
+    recursive: bool,
+    block_size: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum SizeFormat {
+    #[default]
+    Bytes,
+    Si,
+    Human,
+}
+
+/// The entries printed together: the file arguments (`dir` is `None`),
+/// or the contents of one directory
+#[derive(Debug)]
+struct Listing {
+    dir: Option<PathBuf>,
+    entries: Vec<PathBuf>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Checksum {
+    Sha256,
+}
+
+/// Number of hex digits of a checksum shown in the long listing
+const CHECKSUM_LEN: usize = 12;
+
+/// Nerd Font glyphs shown by `--icons`
+const ICON_DIR: &str = "\u{f07b}";
+const ICON_SYMLINK: &str = "\u{f0c1}";
+const ICON_EXECUTABLE: &str = "\u{f489}";
+const ICON_FILE: &str = "\u{f15b}";
+const ICON_EXTENSIONS: [(&str, &str); 8] = [
+    ("rs", "\u{e7a8}"),
+    ("toml", "\u{e615}"),
+    ("json", "\u{e60b}"),
+    ("md", "\u{f48a}"),
+    ("txt", "\u{f15c}"),
+    ("sh", "\u{f489}"),
+    ("py", "\u{e606}"),
+    ("js", "\u{e74e}"),
+];
+
+const SI_UNITS: [&str; 6] = ["kB", "MB", "GB", "TB", "PB", "EB"];
+const HUMAN_UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
 pub fn get_args() -> MyResult<Config> {
     let matches = App::new("lsr")
     .about("Rust ls")
@@ -42,63 +102,488 @@ pub fn get_args() -> MyResult<Config> {
         .long("all")
         .takes_value(false)
     )
+    .arg(
+        Arg::with_name("almost_all")
+        .help("show hidden files except for . and ..")
+        .short("A")
+        .long("almost-all")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("strict")
+        .help("stop at the first error")
+        .long("strict")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("count_only")
+        .help("only print the number of entries")
+        .long("count-only")
+        .takes_value(false)
+        .conflicts_with("long")
+    )
+    .arg(
+        Arg::with_name("si")
+        .help("show sizes in powers of 1000")
+        .long("si")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("human_readable")
+        .help("show sizes in powers of 1024, e.g. 1.5K")
+        .short("h")
+        .long("human-readable")
+        .takes_value(false)
+        .conflicts_with("si")
+    )
+    .arg(
+        Arg::with_name("one_file_system")
+        .help("do not descend into directories on other filesystems")
+        .short("x")
+        .long("one-file-system")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("max_entries")
+        .value_name("N")
+        .help("list at most N entries per directory")
+        .long("max-entries")
+    )
+    .arg(
+        Arg::with_name("min_inode")
+        .value_name("N")
+        .help("only list entries whose inode number is greater than N")
+        .long("min-inode")
+        .visible_alias("since-inode")
+    )
+    .arg(
+        Arg::with_name("relative_time")
+        .help("show modification times relative to now")
+        .long("relative-time")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("checksum")
+        .value_name("ALGORITHM")
+        .help("show a checksum of each file's contents")
+        .long("checksum")
+        .possible_values(&["sha256"])
+    )
+    .arg(
+        Arg::with_name("full_path")
+        .help("show absolute paths")
+        .long("full-path")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("no_group")
+        .help("do not show the group column in long listings")
+        .long("no-group")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("recursive")
+        .help("list subdirectories recursively")
+        .short("R")
+        .long("recursive")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("sort_time")
+        .help("sort by modification time, newest first")
+        .short("t")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("reverse")
+        .help("list entries in reverse order")
+        .short("r")
+        .long("reverse")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("icons")
+        .help("show a Nerd Font icon for each entry's type")
+        .long("icons")
+        .takes_value(false)
+    )
+    .arg(
+        Arg::with_name("block_size")
+        .value_name("SIZE")
+        .help("count the total line in SIZE-byte blocks (default 1024, or 512 with POSIXLY_CORRECT)")
+        .long("block-size")
+        .possible_values(&["512", "1024"])
+    )
+    .arg(
+        Arg::with_name("dereference_size")
+        .help("show the size, time and permissions of symlink targets")
+        .long("dereference-size")
+        .takes_value(false)
+    )
     .get_matches();
 
+    let size_format = if matches.is_present("si") {
+        SizeFormat::Si
+    } else if matches.is_present("human_readable") {
+        SizeFormat::Human
+    } else {
+        SizeFormat::Bytes
+    };
+
+    let max_entries = matches
+        .value_of("max_entries")
+        .map(|val| {
+            val.parse()
+                .map_err(|_| format!("Invalid entry count \"{}\"", val))
+        })
+        .transpose()?;
+
+    let min_inode = matches
+        .value_of("min_inode")
+        .map(|val| {
+            val.parse()
+                .map_err(|_| format!("Invalid inode number \"{}\"", val))
+        })
+        .transpose()?;
+
+    let block_size = match matches.value_of("block_size") {
+        Some("512") => 512,
+        Some(_) => 1024,
+        None if env::var_os("POSIXLY_CORRECT").is_some() => 512,
+        None => 1024,
+    };
+
     Ok(Config{
         paths: matches.values_of_lossy("paths").unwrap(),
         long: matches.is_present("long"),
-        show_hidden: matches.is_present("show_hidden"),
+        show_hidden: matches.is_present("show_hidden") || matches.is_present("almost_all"),
+        strict: matches.is_present("strict"),
+        count_only: matches.is_present("count_only"),
+        size_format,
+        one_file_system: matches.is_present("one_file_system"),
+        dereference_size: matches.is_present("dereference_size"),
+        max_entries,
+        min_inode,
+        relative_time: matches.is_present("relative_time"),
+        checksum: matches.value_of("checksum").map(|_| Checksum::Sha256),
+        full_path: matches.is_present("full_path"),
+        no_group: matches.is_present("no_group"),
+        icons: matches.is_present("icons"),
+        reverse: matches.is_present("reverse"),
+        sort_time: matches.is_present("sort_time"),
+        recursive: matches.is_present("recursive"),
+        block_size,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    let paths = find_files(&config.paths, config.show_hidden)?;
-    if config.long {
-        println!("{}", format_output(&paths)?);
+    if config.count_only {
+        return print_counts(&config);
+    }
+    let mut listings = find_listings(&config.paths, &config)?;
+    // Like ls, a long or recursive listing is split up by directory, while
+    // a plain listing of several arguments is printed as one
+    let per_dir = config.recursive || (config.long && listings.len() > 1);
+    if !per_dir && config.max_entries.is_none() && listings.len() > 1 {
+        let entries = listings.into_iter().flat_map(|listing| listing.entries).collect();
+        listings = vec![Listing { dir: None, entries }];
+    }
+    for listing in &mut listings {
+        sort_paths(&mut listing.entries);
+        if config.sort_time {
+            sort_by_time(&mut listing.entries);
+        }
+        if config.reverse {
+            listing.entries.reverse();
+        }
+    }
+    for (i, listing) in listings.iter().enumerate() {
+        if per_dir {
+            if i > 0 && !config.long {
+                println!();
+            }
+            if let Some(dir) = &listing.dir {
+                println!("{}:", dir.display());
+            }
+        }
+        let group = &listing.entries[..];
+        if config.long && listing.dir.is_some() {
+            println!("total {}", total_blocks(group, &config)?);
+        }
+        let (group, more) = match config.max_entries {
+            Some(max) => (&group[..max.min(group.len())], group.len().saturating_sub(max)),
+            None => (group, 0),
+        };
+        if config.long {
+            println!("{}", format_output(group, &config)?);
+        } else {
+            for path in group {
+                println!("{}", display_path(path, &config)?);
+            }
+        }
+        if more > 0 {
+            println!("... and {} more", more);
+        }
+    }
+    Ok(())
+}
+
+/// The name to print for a path, after its icon with `icons`
+fn display_path(path: &Path, config: &Config) -> MyResult<String> {
+    let name = display_name(path, config)?;
+    if config.icons {
+        Ok(format!("{} {}", icon(path), name))
+    } else {
+        Ok(name)
+    }
+}
+
+/// The path as given, or absolute with `full_path`. Symlinks are only
+/// resolved when their targets are being described.
+fn display_name(path: &Path, config: &Config) -> MyResult<String> {
+    if !config.full_path {
+        return Ok(path.display().to_string());
+    }
+    let full = if config.dereference_size {
+        canonicalize(path)?
     } else {
-        for path in paths {
-            println!("{}", path.display());
+        let parent = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        match path.file_name() {
+            Some(name) => canonicalize(parent)?.join(name),
+            None => canonicalize(path)?,
+        }
+    };
+    Ok(full.display().to_string())
+}
+
+/// Within each directory, put the most recently modified entries first.
+/// Entries whose time cannot be read count as the oldest, and ties keep
+/// their name order.
+fn sort_by_time(paths: &mut [PathBuf]) {
+    paths.sort_by_cached_key(|path| {
+        let modified = path.symlink_metadata().and_then(|meta| meta.modified());
+        (path.parent().map(Path::to_path_buf), Reverse(modified.ok()))
+    });
+}
+
+/// Pick a glyph by file type, then by extension for regular files
+fn icon(path: &Path) -> &'static str {
+    let meta = match path.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(_) => return ICON_FILE,
+    };
+    if meta.file_type().is_symlink() {
+        return ICON_SYMLINK;
+    }
+    if meta.is_dir() {
+        return ICON_DIR;
+    }
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    match ICON_EXTENSIONS.iter().find(|(e, _)| e.eq_ignore_ascii_case(ext)) {
+        Some((_, icon)) => icon,
+        None if meta.mode() & 0o111 != 0 => ICON_EXECUTABLE,
+        None => ICON_FILE,
+    }
+}
+
+/// Sort by directory and then file name, falling back to the full path
+/// so that the output never depends on the order `read_dir` returned the
+/// entries in
+fn sort_paths(paths: &mut [PathBuf]) {
+    paths.sort_by(|a, b| {
+        a.parent()
+            .cmp(&b.parent())
+            .then_with(|| a.file_name().cmp(&b.file_name()))
+            .then_with(|| a.cmp(b))
+    });
+}
+
+fn print_counts(config: &Config) -> MyResult<()> {
+    for path in &config.paths {
+        let mut files = vec![];
+        let mut dirs = vec![];
+        if visit_path(Path::new(path), None, config, &mut files, &mut dirs)? {
+            let count = files.len() + dirs.iter().map(|dir| dir.entries.len()).sum::<usize>();
+            if config.paths.len() > 1 {
+                println!("{}: {}", path, count);
+            } else {
+                println!("{}", count);
+            }
         }
     }
     Ok(())
 }
 
-fn format_output(paths: &[PathBuf]) -> MyResult<String> {
-    //          1   2   3   4   5   6   7   8
-    let fmt = "{:<}{:<} {:>} {:<} {:<} {:>} {:<} {:<}";
-    let mut table = Table::new(fmt);
+fn format_output(paths: &[PathBuf], config: &Config) -> MyResult<String> {
+    let mut fmt = String::from("{:<}{:<} {:>} {:<}");
+    if !config.no_group {
+        fmt.push_str(" {:<}");
+    }
+    fmt.push_str(" {:>} {:<}");
+    if config.checksum.is_some() {
+        fmt.push_str(" {:<}");
+    }
+    fmt.push_str(" {:<}");
+    let mut table = Table::new(&fmt);
 
     for path in paths {
-        let meta = path.metadata()?;
+        let link_meta = path.symlink_metadata()?;
+        let meta = if config.dereference_size {
+            path.metadata().unwrap_or_else(|_| link_meta.clone())
+        } else {
+            link_meta.clone()
+        };
         let uid = meta.uid();
         let user = get_user_by_uid(uid)
             .map(|u| u.name().to_string_lossy().into_owned())
             .unwrap_or_else(|| uid.to_string());
 
-        let gid = meta.gid();
-        let group = get_group_by_gid(gid)
-            .map(|g| g.name().to_string_lossy().into_owned())
-            .unwrap_or_else(|| gid.to_string());
-        
-        let file_type = if path.is_dir() { "d" } else {"-" };
+        let file_type = if link_meta.file_type().is_symlink() {
+            "l"
+        } else if link_meta.is_dir() {
+            "d"
+        } else {
+            "-"
+        };
         let perms = format_mode(meta.mode());
         let modified: DateTime<Local> = DateTime::from(meta.modified()?);
+        let modified = if config.relative_time {
+            humanize_since(modified, Local::now())
+        } else {
+            modified.format("%b %d %y %H:%M").to_string()
+        };
 
-        table.add_row(
-            Row::new()
+        let mut row = Row::new()
             .with_cell(file_type)
-            .with_cell(perms) // 1 "d" or "-"
-            .with_cell(meta.nlink()) // 1 "d" or "-"
-            .with_cell(user) // 1 "d" or "-"
-            .with_cell(group) // 1 "d" or "-"
-            .with_cell(meta.len()) // 1 "d" or "-"
-            .with_cell(modified.format("%b %d %y %H:%M"))
-            .with_cell(path.display()), // 1 "d" or "-"
-        );
+            .with_cell(perms)
+            .with_cell(meta.nlink())
+            .with_cell(user);
+        if !config.no_group {
+            let gid = meta.gid();
+            let group = get_group_by_gid(gid)
+                .map(|g| g.name().to_string_lossy().into_owned())
+                .unwrap_or_else(|| gid.to_string());
+            row.add_cell(group);
+        }
+        // Directory sizes are block counts, so -h leaves them alone
+        let size_format = match config.size_format {
+            SizeFormat::Human if meta.is_dir() => SizeFormat::Bytes,
+            size_format => size_format,
+        };
+        row.add_cell(format_len(meta.len(), size_format));
+        row.add_cell(modified);
+        if let Some(checksum) = config.checksum {
+            let digest = if meta.is_file() {
+                let mut digest = file_checksum(path, checksum)?;
+                digest.truncate(CHECKSUM_LEN);
+                digest
+            } else {
+                "-".to_string()
+            };
+            row.add_cell(digest);
+        }
+        let mut name = display_path(path, config)?;
+        if link_meta.file_type().is_symlink() {
+            name = format!("{} -> {}", name, read_link(path)?.display());
+        }
+        table.add_row(row.with_cell(name));
     }
     Ok(format!("{}", table))
 }
 
+/// The disk usage of `paths` in `block_size` blocks, rounded up,
+/// for the "total" line of a long listing
+fn total_blocks(paths: &[PathBuf], config: &Config) -> MyResult<u64> {
+    let mut blocks = 0;
+    for path in paths {
+        let link_meta = path.symlink_metadata()?;
+        let meta = if config.dereference_size {
+            path.metadata().unwrap_or(link_meta)
+        } else {
+            link_meta
+        };
+        // st_blocks is always in 512-byte units
+        blocks += meta.blocks();
+    }
+    Ok((blocks * 512).div_ceil(config.block_size))
+}
+
+/// Hash a file's contents without reading it all into memory,
+/// returning the digest as lowercase hex
+fn file_checksum(path: &Path, checksum: Checksum) -> MyResult<String> {
+    let mut file = File::open(path)?;
+    match checksum {
+        Checksum::Sha256 => {
+            let mut hasher = Sha256::new();
+            io::copy(&mut file, &mut hasher)?;
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+    }
+}
+
+/// Describe how long before `now` a time was, e.g. "3 days ago"
+fn humanize_since(dt: DateTime<Local>, now: DateTime<Local>) -> String {
+    let secs = now.signed_duration_since(dt).num_seconds();
+    let (value, unit) = match secs {
+        s if s < 60 => return "just now".to_string(),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 60 * 60 * 24 => (s / (60 * 60), "hour"),
+        s if s < 60 * 60 * 24 * 30 => (s / (60 * 60 * 24), "day"),
+        s if s < 60 * 60 * 24 * 365 => (s / (60 * 60 * 24 * 30), "month"),
+        s => (s / (60 * 60 * 24 * 365), "year"),
+    };
+    format!("{} {}{} ago", value, unit, if value == 1 { "" } else { "s" })
+}
+
+fn format_len(len: u64, size_format: SizeFormat) -> String {
+    match size_format {
+        SizeFormat::Bytes => len.to_string(),
+        SizeFormat::Si => format_size(len, 1000, &SI_UNITS),
+        SizeFormat::Human => format_human_size(len),
+    }
+}
+
+/// Scale a byte count by `base` until it fits under it,
+/// e.g. 1500 with base 1000 becomes "1.5kB"
+fn format_size(len: u64, base: u64, units: &[&str]) -> String {
+    let mut value = len as f64;
+    let mut unit = None;
+    for u in units {
+        if value < base as f64 {
+            break;
+        }
+        value /= base as f64;
+        unit = Some(u);
+    }
+    match unit {
+        Some(u) => format!("{:.1}{}", value, u),
+        None => len.to_string(),
+    }
+}
+
+/// A byte count in powers of 1024 as `ls -h` shows it: one decimal
+/// place below 10, e.g. "1.5K", and none above, e.g. "20K"
+fn format_human_size(len: u64) -> String {
+    let mut value = len as f64;
+    let mut unit = None;
+    for u in &HUMAN_UNITS {
+        // Move up a unit when the value would print as 1024, as for 1048575
+        if value.round() < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = Some(u);
+    }
+    match unit {
+        Some(u) if value < 9.95 => format!("{:.1}{}", value, u),
+        Some(u) => format!("{:.0}{}", value, u),
+        None => len.to_string(),
+    }
+}
+
 // assert_eq!(format_mode(0o755), "rwxr-xr-x");
 /// Given a file mode in octal format like 0o752,
 /// return a string like "rwxr-x--x"
@@ -113,49 +598,138 @@ fn format_mode(mode: u32) -> String {
 
 pub fn mk_triple(mode: u32, owner: Owner) -> String {
     let [read, write, execute] = owner.masks();
+    let (special, letter) = owner.special();
+    let execute = match (mode & special != 0, mode & execute != 0) {
+        (true, true) => letter,
+        (true, false) => letter.to_ascii_uppercase(),
+        (false, true) => 'x',
+        (false, false) => '-',
+    };
     format!{
         "{}{}{}",
         if mode & read == 0 { "-" } else { "r" },
         if mode & write == 0 { "-" } else { "w" },
-        if mode & execute == 0 { "-" } else { "x" },
+        execute,
 
     }
 }
 
-fn find_files(
+/// List the file arguments first, then each directory argument, followed
+/// with `recursive` by its subdirectories
+fn find_listings(
     paths: &[String],
-    show_hidden: bool
-) -> MyResult<Vec<PathBuf>> {
-    let mut res = vec![];
+    config: &Config,
+) -> MyResult<Vec<Listing>> {
+    let mut files = vec![];
+    let mut dirs = vec![];
     for path in paths {
-        match metadata(path) {
-            Err(e) => eprintln!("{}: {}", path, e),
-            Ok(meta) => {
-                if meta.is_file() {
-                    res.push(PathBuf::from(path));
-                } else if meta.is_dir() {
-                    for entry in read_dir(path)? {
-                        let entry = entry?;
-                        let is_hidden = entry.file_name().to_str().map(|s| s.starts_with('.')).unwrap_or(false);
-                        if !is_hidden || show_hidden {
-                            res.push(entry.path());
-                        }
-                    }
-                }
+        visit_path(Path::new(path), None, config, &mut files, &mut dirs)?;
+    }
+    let mut listings = vec![];
+    if !files.is_empty() {
+        listings.push(Listing { dir: None, entries: files });
+    }
+    listings.extend(dirs);
+    Ok(listings)
+}
+
+/// Add a file to `files`, or a listing of a directory's entries to `dirs`.
+/// With `recursive`, the listings of its subdirectories (but not of
+/// symlinks to them) follow in name order. Paths at or below `min_inode`
+/// are left out of both. `root_dev` is the device of the path the traversal
+/// started from (`None` when `path` is the start itself). Returns whether
+/// `path` could be listed.
+fn visit_path(
+    path: &Path,
+    root_dev: Option<u64>,
+    config: &Config,
+    files: &mut Vec<PathBuf>,
+    dirs: &mut Vec<Listing>,
+) -> MyResult<bool> {
+    let meta = match metadata(path) {
+        Ok(meta) => meta,
+        Err(e) => return skip_path(path, e, config).map(|_| false),
+    };
+    let root_dev = root_dev.unwrap_or_else(|| meta.dev());
+    if meta.is_file() {
+        if above_min_inode(path, config) {
+            files.push(PathBuf::from(path));
+        }
+    } else if meta.is_dir() && can_descend(root_dev, &meta, config.one_file_system) {
+        let mut entries = match read_entries(path, config) {
+            Ok(entries) => entries,
+            Err(e) => return skip_path(path, e, config).map(|_| false),
+        };
+        let mut subdirs: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.symlink_metadata().is_ok_and(|meta| meta.is_dir()))
+            .cloned()
+            .collect();
+        entries.retain(|entry| above_min_inode(entry, config));
+        dirs.push(Listing { dir: Some(PathBuf::from(path)), entries });
+        if config.recursive {
+            sort_paths(&mut subdirs);
+            for subdir in subdirs {
+                visit_path(&subdir, Some(root_dev), config, files, dirs)?;
             }
         }
     }
-    Ok(res)
+    Ok(true)
+}
+
+/// Whether `path` is listed under `min_inode`, going by the inode of the
+/// path itself rather than a symlink's target
+fn above_min_inode(path: &Path, config: &Config) -> bool {
+    match config.min_inode {
+        Some(min) => path.symlink_metadata().is_ok_and(|meta| meta.ino() > min),
+        None => true,
+    }
+}
+
+/// The entries of a directory, leaving out hidden ones unless `show_hidden`
+fn read_entries(dir: &Path, config: &Config) -> io::Result<Vec<PathBuf>> {
+    let mut entries = vec![];
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let is_hidden = entry.file_name().to_str().map(|s| s.starts_with('.')).unwrap_or(false);
+        if !is_hidden || config.show_hidden {
+            entries.push(entry.path());
+        }
+    }
+    Ok(entries)
+}
+
+/// A path that cannot be listed stops everything with `strict`, and is
+/// otherwise reported and skipped
+fn skip_path(path: &Path, e: io::Error, config: &Config) -> MyResult<()> {
+    if config.strict {
+        return Err(format!("{}: {}", path.display(), e).into());
+    }
+    eprintln!("{}: {}", path.display(), e);
+    Ok(())
+}
+
+/// With `one_file_system`, only directories on the starting device are entered
+fn can_descend(root_dev: u64, meta: &Metadata, one_file_system: bool) -> bool {
+    !one_file_system || meta.dev() == root_dev
 }
 
 #[cfg(test)]
 mod test {
-    use super::{find_files, format_mode, mk_triple, Owner, format_output};
-    use std::path::PathBuf;
+    use super::{can_descend, find_listings, format_human_size, format_mode, format_size, humanize_since, mk_triple, sort_paths, Owner, format_output, Config, MyResult, SI_UNITS};
+    use chrono::{Duration, Local, TimeZone};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use std::{fs::metadata, os::unix::prelude::MetadataExt, path::PathBuf};
+
+    fn find_files(paths: &[String], config: &Config) -> MyResult<Vec<PathBuf>> {
+        let listings = find_listings(paths, config)?;
+        Ok(listings.into_iter().flat_map(|listing| listing.entries).collect())
+    }
+
     #[test]
     fn test_find_files() {
         // Find all non-hidden entries in a directory
-        let res = find_files(&["tests/inputs".to_string()], false);
+        let res = find_files(&["tests/inputs".to_string()], &Config::default());
         assert!(res.is_ok());
         let mut filenames: Vec<_> = res
             .unwrap()
@@ -174,7 +748,7 @@ mod test {
         );
 
         // Any existing file should be found even if hidden
-        let res = find_files(&["tests/inputs/.hidden".to_string()], false);
+        let res = find_files(&["tests/inputs/.hidden".to_string()], &Config::default());
         assert!(res.is_ok());
         let filenames: Vec<_> = res
             .unwrap()
@@ -189,7 +763,7 @@ mod test {
                 "tests/inputs/bustle.txt".to_string(),
                 "tests/inputs/dir".to_string(),
             ],
-            false,
+            &Config::default(),
         );
         assert!(res.is_ok());
         let mut filenames: Vec<_> = res
@@ -204,10 +778,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_find_files_min_inode() {
+        let all = find_files(&["tests/inputs".to_string()], &Config::default()).unwrap();
+        let mut inodes: Vec<_> = all
+            .iter()
+            .map(|path| path.symlink_metadata().unwrap().ino())
+            .collect();
+        inodes.sort();
+        let min = inodes[inodes.len() / 2];
+
+        // Only the entries above the middle inode are left
+        let config = Config { min_inode: Some(min), ..Config::default() };
+        let mut res = find_files(&["tests/inputs".to_string()], &config).unwrap();
+        res.sort();
+        let mut expected: Vec<_> = all
+            .into_iter()
+            .filter(|path| path.symlink_metadata().unwrap().ino() > min)
+            .collect();
+        expected.sort();
+        assert_eq!(res.len(), inodes.len() - inodes.len() / 2 - 1);
+        assert_eq!(res, expected);
+    }
+
     #[test]
     fn test_find_files_hidden() {
         // Find all entries in a directory including hidden
-        let res = find_files(&["tests/inputs".to_string()], true);
+        let res = find_files(
+            &["tests/inputs".to_string()],
+            &Config { show_hidden: true, ..Config::default() },
+        );
         assert!(res.is_ok());
         let mut filenames: Vec<_> = res
             .unwrap()
@@ -227,6 +827,27 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_find_files_strict() {
+        // A bad path is skipped by default
+        let paths = [
+            "tests/inputs/does-not-exist".to_string(),
+            "tests/inputs/empty.txt".to_string(),
+        ];
+        let res = find_files(&paths, &Config::default());
+        assert!(res.is_ok());
+        let filenames: Vec<_> = res
+            .unwrap()
+            .iter()
+            .map(|entry| entry.display().to_string())
+            .collect();
+        assert_eq!(filenames, ["tests/inputs/empty.txt"]);
+
+        // Strict mode stops at the first bad path
+        let res = find_files(&paths, &Config { strict: true, ..Config::default() });
+        assert!(res.is_err());
+    }
+
     fn long_match(
         line: &str,
         expected_name: &str,
@@ -253,7 +874,7 @@ mod test {
         let bustle_path = "tests/inputs/bustle.txt";
         let bustle = PathBuf::from(bustle_path);
 
-        let res = format_output(&[bustle]);
+        let res = format_output(&[bustle], &Config::default());
         assert!(res.is_ok());
 
         let out = res.unwrap();
@@ -270,7 +891,7 @@ mod test {
         let res = format_output(&[
             PathBuf::from("tests/inputs/dir"),
             PathBuf::from("tests/inputs/empty.txt"),
-        ]);
+        ], &Config::default());
         assert!(res.is_ok());
 
         let out = res.unwrap();
@@ -299,9 +920,85 @@ mod test {
         assert_eq!(mk_triple(0o600, Owner::Other), "---");
     }
 
+    #[test]
+    fn test_format_size_si() {
+        assert_eq!(format_size(999, 1000, &SI_UNITS), "999");
+        assert_eq!(format_size(1000, 1000, &SI_UNITS), "1.0kB");
+        assert_eq!(format_size(1500, 1000, &SI_UNITS), "1.5kB");
+        assert_eq!(format_size(1000000, 1000, &SI_UNITS), "1.0MB");
+        assert_eq!(format_size(15000, 1000, &SI_UNITS), "15.0kB");
+    }
+
+    #[test]
+    fn test_format_size_human() {
+        assert_eq!(format_human_size(1023), "1023");
+        assert_eq!(format_human_size(1024), "1.0K");
+        assert_eq!(format_human_size(1536), "1.5K");
+        assert_eq!(format_human_size(10239), "10K");
+        assert_eq!(format_human_size(20 * 1024), "20K");
+        assert_eq!(format_human_size(1048575), "1.0M");
+        assert_eq!(format_human_size(1048576), "1.0M");
+        assert_eq!(format_human_size(4 * 1024 * 1024 * 1024), "4.0G");
+    }
+
+    #[test]
+    fn test_humanize_since() {
+        let now = Local.with_ymd_and_hms(2021, 4, 7, 12, 0, 0).unwrap();
+        let ago = |d: Duration| humanize_since(now - d, now);
+        assert_eq!(ago(Duration::seconds(0)), "just now");
+        assert_eq!(ago(Duration::seconds(59)), "just now");
+        assert_eq!(ago(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(ago(Duration::minutes(45)), "45 minutes ago");
+        assert_eq!(ago(Duration::hours(2)), "2 hours ago");
+        assert_eq!(ago(Duration::days(3)), "3 days ago");
+        assert_eq!(ago(Duration::days(60)), "2 months ago");
+        assert_eq!(ago(Duration::days(365)), "1 year ago");
+        assert_eq!(ago(Duration::days(365 * 5 + 10)), "5 years ago");
+        assert_eq!(humanize_since(now + Duration::hours(1), now), "just now");
+    }
+
+    #[test]
+    fn test_can_descend() {
+        let root = metadata("tests/inputs").unwrap();
+        let dir = metadata("tests/inputs/dir").unwrap();
+        assert!(can_descend(root.dev(), &dir, true));
+        assert!(can_descend(root.dev() + 1, &dir, false));
+        assert!(!can_descend(root.dev() + 1, &dir, true));
+    }
+
+    #[test]
+    fn test_sort_paths_stable() {
+        let expected: Vec<PathBuf> = [
+            "bustle.txt",
+            "fox.txt",
+            "a/bustle.txt",
+            "a/fox.txt",
+            "b/bustle.txt",
+            "tests/inputs/.hidden",
+            "tests/inputs/dir",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        // Any order read_dir might produce sorts to the same output
+        for seed in 0..10 {
+            let mut paths = expected.clone();
+            paths.shuffle(&mut StdRng::seed_from_u64(seed));
+            sort_paths(&mut paths);
+            assert_eq!(paths, expected);
+        }
+    }
+
     #[test]
     fn test_format_mode() {
         assert_eq!(format_mode(0o755), "rwxr-xr-x");
         assert_eq!(format_mode(0o421), "r---w---x");
+        assert_eq!(format_mode(0o4755), "rwsr-xr-x");
+        assert_eq!(format_mode(0o4644), "rwSr--r--");
+        assert_eq!(format_mode(0o2755), "rwxr-sr-x");
+        assert_eq!(format_mode(0o2745), "rwxr-Sr-x");
+        assert_eq!(format_mode(0o1755), "rwxr-xr-t");
+        assert_eq!(format_mode(0o1754), "rwxr-xr-T");
     }
 }
\ No newline at end of file
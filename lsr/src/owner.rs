@@ -13,4 +13,14 @@ impl Owner {
             Self::Other => [0o004, 0o002, 0o001],
         }
     }
+
+    /// The setuid, setgid or sticky bit shown in this owner's execute
+    /// position, and the letter for it when execute is also set
+    pub fn special(&self) -> (u32, char) {
+        match self {
+            Self::User => (0o4000, 's'),
+            Self::Group => (0o2000, 's'),
+            Self::Other => (0o1000, 't'),
+        }
+    }
 }
\ No newline at end of file
@@ -176,8 +176,10 @@ fn dir2_all() -> TestResult {
 fn dir_long(args: &[&str], expected: &[(&str, &str, &str)]) -> TestResult {
     let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
     let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
-    let lines: Vec<&str> =
-        stdout.split("\n").filter(|s| !s.is_empty()).collect();
+    let lines: Vec<&str> = stdout
+        .split("\n")
+        .filter(|s| !s.is_empty() && !s.starts_with("total "))
+        .collect();
     assert_eq!(lines.len(), expected.len());
 
     let mut check = vec![];
@@ -245,3 +247,443 @@ fn dir2_long_all() -> TestResult {
         ],
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn bad_file_strict() -> TestResult {
+    let bad = gen_bad_file();
+    let expected =
+        format!("{}: No such file or directory (os error 2)", &bad);
+    Command::cargo_bin(PRG)?
+        .args(["--strict", &bad, EMPTY])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains(expected));
+
+    Command::cargo_bin(PRG)?
+        .args([&bad, EMPTY])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", EMPTY));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_only() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--count-only", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout("4\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--count-only", "-a", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout("5\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_only_multiple() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--count-only", "-a", "tests/inputs/dir", BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!("tests/inputs/dir: 2\n{}: 1\n", BUSTLE));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_only_min_inode() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut inodes = vec![];
+    for entry in fs::read_dir("tests/inputs")? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            inodes.push(entry.path().symlink_metadata()?.ino());
+        }
+    }
+    inodes.sort();
+    let min = inodes[0];
+    Command::cargo_bin(PRG)?
+        .args(["--count-only", "--min-inode", &min.to_string(), "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", inodes.len() - 1));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_long_si() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--long", "--si", BUSTLE])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let parts: Vec<_> = stdout.split_whitespace().collect();
+    assert_eq!(parts.get(4).unwrap(), &"193");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn long_parts(args: &[&str]) -> Result<Vec<String>, Box<dyn Error>> {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    Ok(stdout.split_whitespace().map(String::from).collect())
+}
+
+#[test]
+fn symlink_long_dereference_size() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let target = fs::canonicalize(BUSTLE)?;
+    let link = dir.path().join("link");
+    std::os::unix::fs::symlink(&target, &link)?;
+    let link = link.to_str().unwrap();
+
+    // The link itself is as long as the path it points to
+    let parts = long_parts(&["--long", link])?;
+    assert!(parts[0].starts_with('l'));
+    assert_eq!(parts[4], target.to_str().unwrap().len().to_string());
+    assert_eq!(parts[parts.len() - 3..], [link, "->", target.to_str().unwrap()]);
+
+    // The target's size is shown but the link is still marked as one
+    let parts = long_parts(&["--long", "--dereference-size", link])?;
+    assert_eq!(parts[0], "lrw-r--r--");
+    assert_eq!(parts[4], "193");
+    assert_eq!(parts[parts.len() - 3], link);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir1_almost_all() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-A", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/.hidden\n\
+            tests/inputs/bustle.txt\n\
+            tests/inputs/dir\n\
+            tests/inputs/empty.txt\n\
+            tests/inputs/fox.txt\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_entries() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    for name in ["e", "d", "c", "b", "a"] {
+        fs::write(dir.path().join(name), "")?;
+    }
+    let path = dir.path().display().to_string();
+    Command::cargo_bin(PRG)?
+        .args(["--max-entries=2", &path])
+        .assert()
+        .success()
+        .stdout(format!("{0}/a\n{0}/b\n... and 3 more\n", path));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn long_relative_time() -> TestResult {
+    let file = tempfile::NamedTempFile::new()?;
+    let path = file.path().display().to_string();
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--relative-time", &path])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("just now {}", path)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_long_checksum() -> TestResult {
+    let parts = long_parts(&["--long", "--checksum=sha256", BUSTLE])?;
+    assert_eq!(parts[parts.len() - 2], "5487b9d9c5d6");
+    assert_eq!(parts.last().unwrap(), BUSTLE);
+
+    let parts = long_parts(&["--long", "--checksum=sha256", "tests/inputs"])?;
+    let dir = parts.iter().position(|p| p == "tests/inputs/dir").unwrap();
+    assert_eq!(parts[dir - 1], "-");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir2_full_path() -> TestResult {
+    let dir = fs::canonicalize("tests/inputs/dir")?;
+    let short = ["--full-path", "tests/inputs/dir"];
+    let long = ["--full-path", "-l", "tests/inputs/dir"];
+    for args in [&short[..], &long[..]] {
+        let parts = long_parts(args)?;
+        let path = parts.last().unwrap();
+        assert!(path.starts_with('/'));
+        assert_eq!(path, &dir.join("spiders.txt").display().to_string());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir1_long_no_group() -> TestResult {
+    let parts = long_parts(&["--long", "--no-group", BUSTLE])?;
+    assert_eq!(parts.len(), 9);
+    assert_eq!(parts[0], "-rw-r--r--");
+    assert_eq!(parts[3], "193");
+    assert_eq!(parts.last().unwrap(), BUSTLE);
+
+    // Every row still starts its path in the same column
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", "--no-group", "tests/inputs"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let starts: Vec<_> = stdout
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with("total "))
+        .map(|line| line.find("tests/inputs/").unwrap())
+        .collect();
+    assert!(starts.iter().all(|start| *start == starts[0]));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn icons() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("src"))?;
+    fs::write(dir.path().join("main.rs"), "")?;
+    let path = dir.path().display().to_string();
+    Command::cargo_bin(PRG)?
+        .args(["--icons", &path])
+        .assert()
+        .success()
+        .stdout(format!("\u{e7a8} {0}/main.rs\n\u{f07b} {0}/src\n", path));
+
+    let parts = long_parts(&["--long", "--icons", &path])?;
+    assert_eq!(parts.last().unwrap(), &format!("{}/src", path));
+    assert_eq!(parts[parts.len() - 2], "\u{f07b}");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir1_reverse() -> TestResult {
+    let expected = [
+        "tests/inputs/bustle.txt",
+        "tests/inputs/dir",
+        "tests/inputs/empty.txt",
+        "tests/inputs/fox.txt",
+    ];
+    Command::cargo_bin(PRG)?
+        .arg("tests/inputs")
+        .assert()
+        .success()
+        .stdout(expected.join("\n") + "\n");
+
+    let reversed: Vec<_> = expected.iter().rev().copied().collect();
+    Command::cargo_bin(PRG)?
+        .args(["-r", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(reversed.join("\n") + "\n");
+
+    let parts = long_parts(&["--long", "--reverse", "tests/inputs"])?;
+    let paths: Vec<_> = parts
+        .iter()
+        .filter(|part| part.starts_with("tests/inputs/"))
+        .map(String::as_str)
+        .collect();
+    assert_eq!(paths, reversed);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_time() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let now = std::time::SystemTime::now();
+    for (name, age) in [("a", 30), ("b", 10), ("c", 20)] {
+        let file = fs::File::create(dir.path().join(name))?;
+        file.set_modified(now - std::time::Duration::from_secs(age * 60))?;
+    }
+    let path = dir.path().display().to_string();
+    Command::cargo_bin(PRG)?
+        .args(["-t", &path])
+        .assert()
+        .success()
+        .stdout(format!("{0}/b\n{0}/c\n{0}/a\n", path));
+
+    Command::cargo_bin(PRG)?
+        .args(["-t", "-r", &path])
+        .assert()
+        .success()
+        .stdout(format!("{0}/a\n{0}/c\n{0}/b\n", path));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dir1_recursive() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-R", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs:\n\
+            tests/inputs/bustle.txt\n\
+            tests/inputs/dir\n\
+            tests/inputs/empty.txt\n\
+            tests/inputs/fox.txt\n\
+            \n\
+            tests/inputs/dir:\n\
+            tests/inputs/dir/spiders.txt\n",
+        );
+
+    // Hidden entries are shown at every level, one table per directory
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--recursive", "--all", "--long", "tests/inputs"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let tables: Vec<_> = stdout.split("\n\n").filter(|t| !t.is_empty()).collect();
+    assert_eq!(tables.len(), 2);
+    assert!(tables[0].starts_with("tests/inputs:\n"));
+    assert!(tables[0].contains("tests/inputs/.hidden\n"));
+    assert!(tables[1].starts_with("tests/inputs/dir:\n"));
+    assert!(tables[1].contains("tests/inputs/dir/.gitkeep\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn symlink_long_in_dir() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("file"), "")?;
+    std::os::unix::fs::symlink("file", dir.path().join("link"))?;
+    let dir = dir.path().to_str().unwrap();
+
+    let cmd = Command::cargo_bin(PRG)?.args(["-l", dir]).assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let link = stdout.lines().find(|line| line.contains("link")).unwrap();
+    assert!(link.starts_with('l'));
+    assert!(link.ends_with(&format!("{}/link -> file", dir)));
+
+    // Only the long listing shows the target
+    Command::cargo_bin(PRG)?
+        .arg(dir)
+        .assert()
+        .success()
+        .stdout(format!("{0}/file\n{0}/link\n", dir));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn totals(args: &[&str]) -> Result<Vec<u64>, Box<dyn Error>> {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    Ok(stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("total "))
+        .map(|blocks| blocks.parse().unwrap())
+        .collect())
+}
+
+#[test]
+fn dir1_long_total() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", "tests/inputs"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let first = stdout.lines().next().unwrap();
+    let blocks = first.strip_prefix("total ").unwrap();
+    assert!(blocks.parse::<u64>().is_ok());
+
+    // 512-byte blocks count twice as many, before rounding up
+    let total_512 = totals(&["-l", "--block-size=512", "tests/inputs"])?;
+    let total_1024 = totals(&["-l", "--block-size=1024", "tests/inputs"])?;
+    assert_eq!(total_1024, [total_512[0].div_ceil(2)]);
+
+    // One total per directory, and none for a file or a short listing
+    assert_eq!(totals(&["-l", "tests/inputs", "tests/inputs/dir"])?.len(), 2);
+    assert_eq!(totals(&["-lR", "tests/inputs"])?.len(), 2);
+    assert!(totals(&["-l", BUSTLE])?.is_empty());
+    assert!(totals(&["tests/inputs"])?.is_empty());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file_and_dir_long_total() -> TestResult {
+    // File arguments come first, in their own table without a total
+    for flags in ["-l", "-lR"] {
+        let cmd = Command::cargo_bin(PRG)?
+            .args([flags, BUSTLE, "tests/inputs/dir"])
+            .assert()
+            .success();
+        let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+        let lines: Vec<_> = stdout.lines().collect();
+        assert!(lines[0].ends_with(BUSTLE));
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "tests/inputs/dir:");
+        assert!(lines[3].starts_with("total "));
+        assert!(lines[4].ends_with("tests/inputs/dir/spiders.txt"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_dir_long_total() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let empty = dir.path().to_str().unwrap();
+    assert_eq!(totals(&["-l", empty])?, [0]);
+
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-l", empty, "tests/inputs/dir"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert!(stdout.starts_with(&format!("{}:\ntotal 0\n\ntests/inputs/dir:\ntotal ", empty)));
+    assert_eq!(totals(&["-l", empty, "tests/inputs/dir"])?.len(), 2);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_per_directory() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("a"))?;
+    fs::create_dir(dir.path().join("b"))?;
+    fs::write(dir.path().join("b/x"), "")?;
+    fs::write(dir.path().join("b/y"), "")?;
+    let dir = dir.path().to_str().unwrap();
+
+    // An empty subdirectory still gets a header
+    Command::cargo_bin(PRG)?
+        .args(["-R", dir])
+        .assert()
+        .success()
+        .stdout(format!("{0}:\n{0}/a\n{0}/b\n\n{0}/a:\n\n{0}/b:\n{0}/b/x\n{0}/b/y\n", dir));
+
+    // -r reverses the entries of each directory, not the directories
+    Command::cargo_bin(PRG)?
+        .args(["-Rr", dir])
+        .assert()
+        .success()
+        .stdout(format!("{0}:\n{0}/b\n{0}/a\n\n{0}/a:\n\n{0}/b:\n{0}/b/y\n{0}/b/x\n", dir));
+    Ok(())
+}
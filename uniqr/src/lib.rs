@@ -1,15 +1,19 @@
 use clap::{App, Arg};
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader, Write};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Config {
     in_file: String,
     out_file: Option<String>,
     count: bool,
+    json: bool,
+    min_count: u64,
+    unique: bool,
+    compare_fields: Option<usize>,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -35,48 +39,201 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .help("precede each output line with the count of the numer of times"),
         )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .conflicts_with("count")
+                .help("print each group of adjacent lines as a JSON object"),
+        )
+        .arg(
+            Arg::with_name("min_count")
+                .value_name("N")
+                .long("min-count")
+                .conflicts_with("json")
+                .help("only print groups of at least N lines"),
+        )
+        .arg(
+            Arg::with_name("unique")
+                .short("u")
+                .long("unique")
+                .takes_value(false)
+                .conflicts_with_all(&["json", "min_count"])
+                .help("only print lines that are not repeated"),
+        )
+        .arg(
+            Arg::with_name("compare_fields")
+                .value_name("N")
+                .long("compare-fields")
+                .help("compare only the first N whitespace-separated fields"),
+        )
         .get_matches();
+    let min_count = matches
+        .value_of("min_count")
+        .map(|val| {
+            val.parse()
+                .map_err(|_| format!("Invalid count \"{}\"", val))
+        })
+        .transpose()?;
+    let compare_fields = matches
+        .value_of("compare_fields")
+        .map(|val| match val.parse() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("Invalid field count \"{}\"", val)),
+        })
+        .transpose()?;
     Ok(Config {
         in_file: matches.value_of_lossy("input_file").unwrap().to_string(),
         out_file: matches.value_of("out_file").map(String::from),
         count: matches.is_present("count"),
+        json: matches.is_present("json"),
+        min_count: min_count.unwrap_or(1),
+        unique: matches.is_present("unique"),
+        compare_fields,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
+    if let Some(out_name) = &config.out_file {
+        if is_same_file(&config.in_file, out_name) {
+            return Err(format!("{}: input and output are the same file", out_name).into());
+        }
+    }
     let mut file = open(&config.in_file).map_err(|e| format!("{}: {}", config.in_file, e))?;
-    let mut out_file: Box<dyn Write> = match config.out_file {
+    let mut out_file: Box<dyn Write> = match &config.out_file {
         Some(out_name) => Box::new(File::create(out_name)?),
         _ => Box::new(io::stdout()),
     };
-    let mut line_cur = String::new();
-    let mut line_prev = String::new();
-    let mut count: u64 = 0;
-    let mut print = |count: u64, text: &str| -> MyResult<()> {
-        if config.count {
-            write!(out_file, "{:>4} {}", count, text)?;
-        } else {
-            write!(out_file, "{}", text)?;
+    let mut read_err = None;
+    let lines = std::iter::from_fn(|| {
+        let mut line = String::new();
+        match file.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(line),
+            Err(e) => {
+                read_err = Some(e);
+                None
+            }
         }
-        Ok(())
-    };
-    loop {
-        let byte = file.read_line(&mut line_cur)?;
-        if count == 0 || line_prev.trim_end() == line_cur.trim_end() {
-            count += 1;
-        } else {
-            print(count, &line_prev)?;
-            count = 1;
+    });
+    let groups = dedup_adjacent(lines, &config);
+    if config.json {
+        write!(out_file, "{}", format_json(groups))?;
+    } else {
+        let groups = groups
+            .filter(|(count, _)| *count >= config.min_count && (!config.unique || *count == 1));
+        for (count, line) in groups {
+            if config.count {
+                write!(out_file, "{:>4} {}", count, line)?;
+            } else {
+                write!(out_file, "{}", line)?;
+            }
         }
-        if count <= 1 {
-            line_prev = line_cur.clone();
+    }
+    match read_err {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+/// Collapse runs of adjacent matching lines into `(count, line)` pairs,
+/// keeping the first line of each run as it was read.
+pub fn dedup_adjacent<'a, I: Iterator<Item = String> + 'a>(
+    iter: I,
+    cfg: &'a Config,
+) -> impl Iterator<Item = (u64, String)> + 'a {
+    DedupAdjacent {
+        iter,
+        cfg,
+        pending: None,
+    }
+}
+
+struct DedupAdjacent<'a, I> {
+    iter: I,
+    cfg: &'a Config,
+    pending: Option<String>,
+}
+
+impl<'a, I: Iterator<Item = String>> Iterator for DedupAdjacent<'a, I> {
+    type Item = (u64, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.pending.take().or_else(|| self.iter.next())?;
+        let mut count = 1;
+        loop {
+            match self.iter.next() {
+                Some(line) if self.cfg.is_same_line(&first, &line) => count += 1,
+                other => {
+                    self.pending = other;
+                    break;
+                }
+            }
         }
-        line_cur.clear();
-        if byte == 0 {
-            break;
+        Some((count, first))
+    }
+}
+
+impl Config {
+    fn is_same_line(&self, a: &str, b: &str) -> bool {
+        self.compare_key(a) == self.compare_key(b)
+    }
+
+    /// The parts of a line that take part in the comparison
+    fn compare_key<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self.compare_fields {
+            Some(n) => line.split_whitespace().take(n).collect(),
+            None => vec![line.trim_end()],
         }
     }
-    Ok(())
+}
+
+fn format_json(groups: impl Iterator<Item = (u64, String)>) -> String {
+    let mut line_num = 1;
+    let objects: Vec<String> = groups
+        .map(|(count, line)| {
+            let object = format!(
+                "  {{\"line\": \"{}\", \"count\": {}, \"first_line_number\": {}}}",
+                json_escape(line.trim_end_matches(&['\r', '\n'][..])),
+                count,
+                line_num
+            );
+            line_num += count;
+            object
+        })
+        .collect();
+    if objects.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", objects.join(",\n"))
+    }
+}
+
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn is_same_file(in_file: &str, out_file: &str) -> bool {
+    let in_path = match in_file {
+        "-" => "/dev/stdin",
+        _ => in_file,
+    };
+    match (fs::canonicalize(in_path), fs::canonicalize(out_file)) {
+        (Ok(in_path), Ok(out_path)) => in_path == out_path,
+        _ => false,
+    }
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -85,3 +242,88 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
         _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{dedup_adjacent, format_json, json_escape, Config};
+
+    fn dedup(lines: &[&str]) -> Vec<(u64, String)> {
+        let config = Config::default();
+        let lines: Vec<String> = lines.iter().map(|s| s.to_string()).collect();
+        dedup_adjacent(lines.into_iter(), &config).collect()
+    }
+
+    #[test]
+    fn test_dedup_adjacent_empty() {
+        assert_eq!(dedup(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_dedup_adjacent_singletons() {
+        assert_eq!(
+            dedup(&["a\n", "b\n", "a\n"]),
+            vec![
+                (1, "a\n".to_string()),
+                (1, "b\n".to_string()),
+                (1, "a\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedup_adjacent_groups() {
+        assert_eq!(
+            dedup(&["a\n", "a\n", "b\n", "c\n", "c\n", "c"]),
+            vec![
+                (2, "a\n".to_string()),
+                (1, "b\n".to_string()),
+                (3, "c\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedup_adjacent_line_endings() {
+        // Runs compare equal regardless of the line terminator
+        assert_eq!(
+            dedup(&["a\r\n", "a\n", "a"]),
+            vec![(3, "a\r\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_dedup_adjacent_compare_fields() {
+        let config = Config {
+            compare_fields: Some(2),
+            ..Config::default()
+        };
+        let lines = ["GET /a 200\n", "GET /a 404\n", "GET /b 200\n", "GET\n"];
+        let lines = lines.iter().map(|s| s.to_string());
+        assert_eq!(
+            dedup_adjacent(lines, &config).collect::<Vec<_>>(),
+            vec![
+                (2, "GET /a 200\n".to_string()),
+                (1, "GET /b 200\n".to_string()),
+                (1, "GET\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_escape() {
+        assert_eq!(json_escape("plain"), "plain");
+        assert_eq!(json_escape("say \"hi\""), "say \\\"hi\\\"");
+        assert_eq!(json_escape("a\\b\tc"), "a\\\\b\\tc");
+        assert_eq!(json_escape("\u{1}"), "\\u0001");
+    }
+
+    #[test]
+    fn test_format_json() {
+        assert_eq!(format_json(vec![].into_iter()), "[]\n");
+        assert_eq!(
+            format_json(vec![(2, "a\n".to_string()), (1, "b".to_string())].into_iter()),
+            "[\n  {\"line\": \"a\", \"count\": 2, \"first_line_number\": 1},\n  \
+            {\"line\": \"b\", \"count\": 1, \"first_line_number\": 3}\n]\n"
+        );
+    }
+}
@@ -605,3 +605,118 @@ fn t6_outfile_count() -> TestResult {
 fn t6_stdin_outfile_count() -> TestResult {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+#[test]
+fn json_groups() -> TestResult {
+    let expected = r#"[
+  {"line": "a", "count": 2, "first_line_number": 1},
+  {"line": "say \"b\"", "count": 1, "first_line_number": 3},
+  {"line": "c", "count": 3, "first_line_number": 4}
+]
+"#;
+    Command::cargo_bin(PRG)?
+        .arg("--json")
+        .write_stdin("a\na\nsay \"b\"\nc\nc\nc")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_same_in_and_out_file() -> TestResult {
+    let input = fs::read_to_string(T1.input)?;
+    let file = NamedTempFile::new()?;
+    fs::write(file.path(), &input)?;
+    let path = file.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([path, path])
+        .assert()
+        .failure()
+        .stderr(format!("{}: input and output are the same file\n", path));
+    assert_eq!(fs::read_to_string(path)?, input);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn dies_stdin_is_out_file() -> TestResult {
+    let input = fs::read_to_string(T1.input)?;
+    let file = NamedTempFile::new()?;
+    fs::write(file.path(), &input)?;
+    let path = file.path().to_str().unwrap();
+    let prg = assert_cmd::cargo::cargo_bin(PRG);
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} - {} < {}", prg.display(), path, path))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "input and output are the same file",
+        ));
+    assert_eq!(fs::read_to_string(path)?, input);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn min_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--min-count=2"])
+        .write_stdin("a\na\nb\nc\nc\nc\nb\n")
+        .assert()
+        .success()
+        .stdout("   2 a\n   3 c\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_min_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--min-count", "foo"])
+        .assert()
+        .failure()
+        .stderr("Invalid count \"foo\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn compare_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--compare-fields", "2"])
+        .write_stdin("10:01 GET /a\n10:01 GET /b\n10:02 GET /a\n10:02 GET  /a\n")
+        .assert()
+        .success()
+        .stdout("   2 10:01 GET /a\n   2 10:02 GET /a\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unique() -> TestResult {
+    for flag in ["-u", "--unique"] {
+        Command::cargo_bin(PRG)?
+            .args(["-c", flag])
+            .write_stdin("a\na\nb\nc\nc\nc\nb\nd")
+            .assert()
+            .success()
+            .stdout("   1 b\n   1 b\n   1 d");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unique_and_min_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-u", "--min-count=2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
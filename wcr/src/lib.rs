@@ -1,26 +1,84 @@
 use clap::{App, Arg};
 use core::str;
+use std::cmp::Reverse;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use walkdir::WalkDir;
+
+/// Bytes read at a time by `count`
+const CHUNK_SIZE: usize = 64 * 1024;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
+    files0_from: Option<String>,
     lines: bool,
     words: bool,
+    word_mode: WordMode,
     bytes: bool,
     chars: bool,
+    max_line: bool,
+    sort_name: bool,
+    sort_by: Option<SortKey>,
+    reverse: bool,
+    total: TotalMode,
+    percent: bool,
+    aggregate: bool,
+    any_newline: bool,
+    recursive: bool,
+    ascii_only: bool,
+    display_width: bool,
+    expect_lines: Option<usize>,
+    expect_words: Option<usize>,
+    expect_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SortKey {
+    Lines,
+    Words,
+    Bytes,
+    Chars,
+    Name,
+}
+
+/// How a line is split into words
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordMode {
+    /// Runs of non-whitespace, like `wc`
+    Whitespace,
+    /// Words as found by Unicode (UAX #29) word segmentation, which skips
+    /// runs of punctuation
+    Unicode,
+}
+
+/// When to print the total line
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TotalMode {
+    /// Only when there is more than one input
+    Auto,
+    Always,
+    /// Print the total and nothing else
+    Only,
+    Never,
 }
 
+/// Counts for one input: `num_bytes` is raw bytes and `num_chars` is
+/// `char`s, so they differ for non-ASCII text
 #[derive(Debug, PartialEq)]
 pub struct FileInfo {
-    num_lines: usize,
-    num_words: usize,
-    num_bytes: usize,
-    num_chars: usize,
+    pub num_lines: usize,
+    pub num_words: usize,
+    pub num_bytes: usize,
+    pub num_chars: usize,
+    pub num_non_ascii: usize,
+    pub max_display_width: usize,
+    pub max_line_len: usize,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -35,6 +93,13 @@ pub fn get_args() -> MyResult<Config> {
                 .multiple(true)
                 .default_value("-"),
         )
+        .arg(
+            Arg::with_name("files0_from")
+                .value_name("LIST")
+                .long("files0-from")
+                .help("Read the NUL-separated input file names from LIST (- for stdin)")
+                .conflicts_with("files"),
+        )
         .arg(
             Arg::with_name("lines")
                 .long("lines")
@@ -44,11 +109,19 @@ pub fn get_args() -> MyResult<Config> {
         )
         .arg(
             Arg::with_name("words")
-                .long("words")
                 .short("w")
                 .help("The number of words in each input file")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("word_mode")
+                .value_name("MODE")
+                .long("words")
+                .help("Like -w, splitting words on whitespace or Unicode word boundaries")
+                .possible_values(&["whitespace", "unicode"])
+                .require_equals(true)
+                .min_values(0),
+        )
         .arg(
             Arg::with_name("bytes")
                 .long("bytes")
@@ -64,73 +137,367 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .conflicts_with("bytes"),
         )
+        .arg(
+            Arg::with_name("max_line")
+                .long("max-line-length")
+                .short("L")
+                .help("The number of characters in the longest line of each input file")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sort_name")
+                .long("sort-name")
+                .help("Sort the output by filename")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sort_by")
+                .value_name("KEY")
+                .long("sort-by")
+                .help("Sort the output by a count, largest first, or by filename")
+                .possible_values(&["lines", "words", "bytes", "chars", "name"])
+                .conflicts_with("sort_name"),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .long("reverse")
+                .help("Reverse the order of the output")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("total")
+                .value_name("WHEN")
+                .long("total")
+                .help("When to print a line with total counts")
+                .possible_values(&["auto", "always", "only", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("percent")
+                .long("percent")
+                .help("Show each file's share of the total of the first count")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("aggregate")
+                .long("aggregate")
+                .help("Sum the \"LINES WORDS BYTES [FILE]\" rows of wc output read from stdin")
+                .takes_value(false)
+                .conflicts_with_all(&["files", "recursive", "chars", "percent"]),
+        )
+        .arg(
+            Arg::with_name("any_newline")
+                .long("any-newline")
+                .help("Treat \\n, \\r\\n and a lone \\r as line terminators")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .long("recursive")
+                .short("r")
+                .help("Count every file under each directory")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("ascii_only")
+                .long("ascii-only")
+                .help("Also report the number of non-ASCII bytes in each input file")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("display_width")
+                .long("display-width")
+                .help("Also report the display width of the widest line in each input file")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("expect_lines")
+                .value_name("LINES")
+                .long("expect-lines")
+                .help("Fail unless each input file has LINES lines"),
+        )
+        .arg(
+            Arg::with_name("expect_words")
+                .value_name("WORDS")
+                .long("expect-words")
+                .help("Fail unless each input file has WORDS words"),
+        )
+        .arg(
+            Arg::with_name("expect_bytes")
+                .value_name("BYTES")
+                .long("expect-bytes")
+                .help("Fail unless each input file has BYTES bytes"),
+        )
         .get_matches();
 
     let mut lines = matches.is_present("lines");
-    let mut words = matches.is_present("words");
+    let mut words = matches.is_present("words") || matches.is_present("word_mode");
     let mut bytes = matches.is_present("bytes");
     let chars = matches.is_present("chars");
+    let max_line = matches.is_present("max_line");
 
-    if [lines, words, bytes, chars].iter().all(|v| v == &false) {
+    if [lines, words, bytes, chars, max_line]
+        .iter()
+        .all(|v| v == &false)
+    {
         lines = true;
         words = true;
         bytes = true;
     }
 
+    let expect_lines = matches
+        .value_of("expect_lines")
+        .map(parse_count)
+        .transpose()?;
+    let expect_words = matches
+        .value_of("expect_words")
+        .map(parse_count)
+        .transpose()?;
+    let expect_bytes = matches
+        .value_of("expect_bytes")
+        .map(parse_count)
+        .transpose()?;
+
+    let word_mode = match matches.value_of("word_mode") {
+        Some("unicode") => WordMode::Unicode,
+        _ => WordMode::Whitespace,
+    };
+
+    let sort_by = matches.value_of("sort_by").map(|key| match key {
+        "lines" => SortKey::Lines,
+        "words" => SortKey::Words,
+        "bytes" => SortKey::Bytes,
+        "chars" => SortKey::Chars,
+        _ => SortKey::Name,
+    });
+
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
+        files0_from: matches.value_of("files0_from").map(String::from),
         lines,
         words,
+        word_mode,
         bytes,
         chars,
+        max_line,
+        sort_name: matches.is_present("sort_name"),
+        sort_by,
+        reverse: matches.is_present("reverse"),
+        percent: matches.is_present("percent"),
+        aggregate: matches.is_present("aggregate"),
+        total: match matches.value_of("total") {
+            Some("always") => TotalMode::Always,
+            Some("only") => TotalMode::Only,
+            Some("never") => TotalMode::Never,
+            _ => TotalMode::Auto,
+        },
+        any_newline: matches.is_present("any_newline"),
+        recursive: matches.is_present("recursive"),
+        ascii_only: matches.is_present("ascii_only"),
+        display_width: matches.is_present("display_width"),
+        expect_lines,
+        expect_words,
+        expect_bytes,
     })
 }
 
+fn parse_count(val: &str) -> MyResult<usize> {
+    val.parse()
+        .map_err(|_| format!("Invalid count \"{}\"", val).into())
+}
+
 pub fn run(config: Config) -> MyResult<()> {
     // println!("{:#?}", config);
-    let file_num = config.files.len();
+    if config.aggregate {
+        let (lines, words, bytes) = aggregate(io::stdin().lock())?;
+        let counts: Vec<_> = [
+            (lines, config.lines),
+            (words, config.words),
+            (bytes, config.bytes),
+        ]
+        .iter()
+        .filter(|(_, show)| *show)
+        .map(|(count, _)| *count)
+        .collect();
+        println!("{}", format_row(&counts, field_width(&counts), "total"));
+        return Ok(());
+    }
+    let files = match &config.files0_from {
+        Some(list) => read_files0(list)?,
+        None => config.files.clone(),
+    };
+    let files = if config.recursive {
+        find_files(&files)
+    } else {
+        files
+    };
+    let file_num = files.len();
     let mut total_lines = 0;
     let mut total_words = 0;
     let mut total_bytes = 0;
     let mut total_chars = 0;
+    let mut total_non_ascii = 0;
+    let mut max_display_width = 0;
+    let mut max_line_len = 0;
 
-    for filename in config.files {
-        match open(&filename) {
+    let mut results = vec![];
+    let mut mismatches = vec![];
+    for filename in &files {
+        match open(filename) {
             Err(e) => eprint!("{}: {}", filename, e),
             Ok(file) => {
-                if let Ok(fileinfo) = count(file) {
-                    println!(
-                        "{}{}{}{}{}",
-                        format_field(fileinfo.num_lines, config.lines),
-                        format_field(fileinfo.num_words, config.words),
-                        format_field(fileinfo.num_bytes, config.bytes),
-                        format_field(fileinfo.num_chars, config.chars),
-                        if filename == "-" {
-                            "".to_string()
-                        } else {
-                            format!(" {}", filename)
-                        }
-                    );
+                if let Ok(fileinfo) = count_with_mode(file, config.any_newline, config.word_mode) {
                     total_lines += fileinfo.num_lines;
                     total_words += fileinfo.num_words;
                     total_bytes += fileinfo.num_bytes;
                     total_chars += fileinfo.num_chars;
+                    total_non_ascii += fileinfo.num_non_ascii;
+                    max_display_width = max_display_width.max(fileinfo.max_display_width);
+                    max_line_len = max_line_len.max(fileinfo.max_line_len);
+                    mismatches.extend(check_expected(filename, &fileinfo, &config));
+                    results.push((filename.to_string(), fileinfo));
                 }
             }
         }
     }
-    if file_num > 1 {
-        println!(
-            "{}{}{}{}{}",
-            format_field(total_lines, config.lines),
-            format_field(total_words, config.words),
-            format_field(total_bytes, config.bytes),
-            format_field(total_chars, config.chars),
-            " total"
-        );
+    if config.sort_name {
+        sort_by_name(&mut results);
+    }
+    if let Some(key) = config.sort_by {
+        sort_by_key(&mut results, key);
+    }
+    if config.reverse {
+        results.reverse();
+    }
+    if config.total == TotalMode::Only {
+        results.clear();
+    }
+    let total = FileInfo {
+        num_lines: total_lines,
+        num_words: total_words,
+        num_bytes: total_bytes,
+        num_chars: total_chars,
+        num_non_ascii: total_non_ascii,
+        max_display_width,
+        max_line_len,
+    };
+    let total_counts = shown_counts(&total, &config);
+    let mut rows: Vec<_> = results
+        .iter()
+        .map(|(filename, fileinfo)| (shown_counts(fileinfo, &config), filename.to_string()))
+        .collect();
+    let show_total = match config.total {
+        TotalMode::Auto => file_num > 1,
+        TotalMode::Always | TotalMode::Only => true,
+        TotalMode::Never => false,
+    };
+    if show_total {
+        rows.push((total_counts.clone(), "total".to_string()));
+    }
+    if config.percent {
+        for (counts, name) in &mut rows {
+            let percent = format_percent(counts[0], total_counts[0]);
+            *name = match name.as_str() {
+                "-" => percent,
+                _ => format!("{} {}", percent, name),
+            };
+        }
+    }
+    // Every column is as wide as the largest number printed, like coreutils
+    let all_counts: Vec<_> = rows
+        .iter()
+        .flat_map(|(counts, _)| counts)
+        .copied()
+        .collect();
+    let width = field_width(&all_counts);
+    for (counts, name) in rows {
+        println!("{}", format_row(&counts, width, &name));
     }
 
-    Ok(())
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches.join("\n").into())
+    }
+}
+
+/// Sum the lines, words and bytes of wc output rows. Rows named "total"
+/// are skipped so that the output of a multi-file wc is not counted twice.
+fn aggregate(input: impl BufRead) -> MyResult<(usize, usize, usize)> {
+    let mut totals = (0, 0, 0);
+    for (i, row) in input.lines().enumerate() {
+        let row = row?;
+        let fields: Vec<_> = row.split_whitespace().collect();
+        let counts: Option<Vec<usize>> = fields.iter().take(3).map(|f| f.parse().ok()).collect();
+        match (counts.as_deref(), fields.get(3..)) {
+            _ if fields.is_empty() => continue,
+            (_, Some(["total"])) => continue,
+            (Some(&[lines, words, bytes]), Some(_)) => {
+                totals.0 += lines;
+                totals.1 += words;
+                totals.2 += bytes;
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: expected \"LINES WORDS BYTES [FILE]\", found \"{}\"",
+                    i + 1,
+                    row
+                )
+                .into())
+            }
+        }
+    }
+    Ok(totals)
+}
+
+/// The file names in a NUL-separated list, skipping empty entries
+fn read_files0(list: &str) -> MyResult<Vec<String>> {
+    let mut file = open(list).map_err(|e| format!("{}: {}", list, e))?;
+    let mut buf = vec![];
+    file.read_to_end(&mut buf)?;
+    Ok(buf
+        .split(|b| *b == b'\0')
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect())
+}
+
+fn find_files(paths: &[String]) -> Vec<String> {
+    let mut files = vec![];
+    for path in paths {
+        if path == "-" {
+            files.push(path.to_string());
+            continue;
+        }
+        for entry in WalkDir::new(path).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
+            match entry {
+                Err(e) => eprintln!("{}", e),
+                Ok(entry) => {
+                    if !entry.file_type().is_dir() {
+                        files.push(entry.path().display().to_string());
+                    }
+                }
+            }
+        }
+    }
+    files
+}
+
+fn check_expected(filename: &str, fileinfo: &FileInfo, config: &Config) -> Vec<String> {
+    [
+        (config.expect_lines, fileinfo.num_lines, "lines"),
+        (config.expect_words, fileinfo.num_words, "words"),
+        (config.expect_bytes, fileinfo.num_bytes, "bytes"),
+    ]
+    .iter()
+    .filter_map(|(expected, actual, name)| match expected {
+        Some(expected) if expected != actual => Some(format!(
+            "{}: expected {} {}, found {}",
+            filename, expected, name, actual
+        )),
+        _ => None,
+    })
+    .collect()
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -140,68 +507,446 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn format_field(value: usize, show: bool) -> String {
-    if show {
-        format!("{:>8}", value)
-    } else {
-        "".to_string()
+fn sort_by_name(results: &mut [(String, FileInfo)]) {
+    results.sort_by_key(|(filename, _)| filename.to_lowercase());
+}
+
+/// Sort by a count, largest first, or by name
+fn sort_by_key(results: &mut [(String, FileInfo)], key: SortKey) {
+    match key {
+        SortKey::Lines => results.sort_by_key(|(_, info)| Reverse(info.num_lines)),
+        SortKey::Words => results.sort_by_key(|(_, info)| Reverse(info.num_words)),
+        SortKey::Bytes => results.sort_by_key(|(_, info)| Reverse(info.num_bytes)),
+        SortKey::Chars => results.sort_by_key(|(_, info)| Reverse(info.num_chars)),
+        SortKey::Name => sort_by_name(results),
     }
 }
 
-fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
+/// The counts selected by the options, in output order
+fn shown_counts(fileinfo: &FileInfo, config: &Config) -> Vec<usize> {
+    [
+        (fileinfo.num_lines, config.lines),
+        (fileinfo.num_words, config.words),
+        (fileinfo.num_bytes, config.bytes),
+        (fileinfo.num_chars, config.chars),
+        (fileinfo.num_non_ascii, config.ascii_only),
+        (fileinfo.max_display_width, config.display_width),
+        (fileinfo.max_line_len, config.max_line),
+    ]
+    .iter()
+    .filter(|(_, show)| *show)
+    .map(|(count, _)| *count)
+    .collect()
+}
 
-    let mut line = String::new();
+/// The number of digits in the largest count
+fn field_width(counts: &[usize]) -> usize {
+    counts
+        .iter()
+        .map(|count| count.to_string().len())
+        .max()
+        .unwrap_or(1)
+}
+
+fn format_field(value: usize, width: usize) -> String {
+    format!("{:>width$}", value, width = width)
+}
+
+/// The share of `total` that `part` is, as a right-aligned percentage
+fn format_percent(part: usize, total: usize) -> String {
+    let percent = match total {
+        0 => 0.0,
+        _ => part as f64 * 100.0 / total as f64,
+    };
+    format!("{:>5.1}%", percent)
+}
+
+/// Space-separated counts followed by the name, which is left off for stdin
+fn format_row(counts: &[usize], width: usize, name: &str) -> String {
+    let mut row = counts
+        .iter()
+        .map(|count| format_field(*count, width))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if name != "-" {
+        row.push(' ');
+        row.push_str(name);
+    }
+    row
+}
+
+/// Count the contents of a string
+///
+/// ```
+/// let info = wcr::count_str("one two\nthree\n").unwrap();
+/// assert_eq!(info.num_lines, 2);
+/// assert_eq!(info.num_words, 3);
+/// assert_eq!(info.num_bytes, 14);
+/// ```
+pub fn count_str(s: &str) -> MyResult<FileInfo> {
+    count(Cursor::new(s), false)
+}
+
+/// Count lines, words, bytes and chars read from `file`. With
+/// `any_newline`, a lone `\r` also ends a line.
+pub fn count(file: impl BufRead, any_newline: bool) -> MyResult<FileInfo> {
+    count_with_mode(file, any_newline, WordMode::Whitespace)
+}
+
+/// Like `count`, splitting words as `word_mode` says.
+///
+/// The input is read in chunks of `CHUNK_SIZE` bytes. A line is only
+/// kept in memory up to a space or tab past its first `CHUNK_SIZE` bytes,
+/// where the text so far can be measured on its own.
+pub fn count_with_mode(
+    mut file: impl BufRead,
+    any_newline: bool,
+    word_mode: WordMode,
+) -> MyResult<FileInfo> {
+    let mut counter = Counter::new(any_newline, word_mode);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    // The start of a UTF-8 sequence cut off at the end of the last chunk
+    let mut pending = vec![];
 
     loop {
-        let line_bytes = file.read_line(&mut line)?;
-        match line_bytes {
-            0 => break,
-            _ => {
-                num_bytes += line_bytes;
-                num_words += line.split_whitespace().count();
-                num_lines += 1;
-                num_chars += line.chars().count();
-                line.clear();
+        let num_read = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        counter.info.num_bytes += num_read;
+        pending.extend_from_slice(&chunk[..num_read]);
+        let valid = match str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e).into()),
+        };
+        let (text, rest) = pending.split_at(valid);
+        counter.add(str::from_utf8(text)?);
+        pending = rest.to_vec();
+    }
+    if let Err(e) = str::from_utf8(&pending) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, e).into());
+    }
+
+    Ok(counter.finish())
+}
+
+/// Running counts for `count`, fed one piece of text at a time
+struct Counter {
+    any_newline: bool,
+    word_mode: WordMode,
+    info: FileInfo,
+    in_word: bool,
+    /// Whether the current line has any chars yet
+    in_line: bool,
+    /// With `any_newline`, the last char was a `\r` that ended a line
+    after_cr: bool,
+    /// Length and width of the part of the line already measured
+    line_len: usize,
+    line_width: usize,
+    /// The rest of the current line. Its width is measured as a whole, as
+    /// joined emoji and variation selectors change the width of a sequence.
+    line: String,
+}
+
+impl Counter {
+    fn new(any_newline: bool, word_mode: WordMode) -> Self {
+        Counter {
+            any_newline,
+            word_mode,
+            info: FileInfo {
+                num_lines: 0,
+                num_words: 0,
+                num_bytes: 0,
+                num_chars: 0,
+                num_non_ascii: 0,
+                max_display_width: 0,
+                max_line_len: 0,
+            },
+            in_word: false,
+            in_line: false,
+            after_cr: false,
+            line_len: 0,
+            line_width: 0,
+            line: String::new(),
+        }
+    }
+
+    fn add(&mut self, text: &str) {
+        for c in text.chars() {
+            // Unicode scalar values, not user-perceived characters: an
+            // "é" written as "e" plus a combining accent counts as two
+            self.info.num_chars += 1;
+            if !c.is_ascii() {
+                self.info.num_non_ascii += c.len_utf8();
+            }
+            if self.word_mode == WordMode::Whitespace {
+                let in_word = !c.is_whitespace();
+                if in_word && !self.in_word {
+                    self.info.num_words += 1;
+                }
+                self.in_word = in_word;
+            }
+
+            let after_cr = self.after_cr;
+            self.after_cr = false;
+            match c {
+                // The second half of a "\r\n" that already ended the line
+                '\n' if after_cr => {}
+                '\n' => {
+                    self.line.push(c);
+                    self.end_line();
+                }
+                '\r' if self.any_newline => {
+                    self.line.push(c);
+                    self.end_line();
+                    self.after_cr = true;
+                }
+                _ => {
+                    self.in_line = true;
+                    self.line.push(c);
+                    // Nothing joins across a space or tab, so a long line
+                    // can be measured a piece at a time
+                    if (c == ' ' || c == '\t') && self.line.len() >= CHUNK_SIZE {
+                        self.measure(false);
+                    }
+                }
             }
         }
     }
 
-    Ok(FileInfo {
-        num_lines,
-        num_words,
-        num_bytes,
-        num_chars,
-    })
+    /// Add the buffered part of the line to its length, width and words,
+    /// leaving out the line terminator at the end of the line
+    fn measure(&mut self, end_of_line: bool) {
+        let line = std::mem::take(&mut self.line);
+        let text = if end_of_line {
+            line.trim_end_matches(&['\r', '\n'][..])
+        } else {
+            &line
+        };
+        self.line_len += text.chars().count();
+        self.line_width += text.width();
+        if self.word_mode == WordMode::Unicode {
+            self.info.num_words += text.unicode_words().count();
+        }
+        // Keep the allocation for the rest of the line
+        self.line = line;
+        self.line.clear();
+    }
+
+    fn end_line(&mut self) {
+        self.measure(true);
+        self.info.num_lines += 1;
+        self.info.max_display_width = self.info.max_display_width.max(self.line_width);
+        self.info.max_line_len = self.info.max_line_len.max(self.line_len);
+        self.in_line = false;
+        self.line_len = 0;
+        self.line_width = 0;
+    }
+
+    fn finish(mut self) -> FileInfo {
+        if self.in_line {
+            self.end_line();
+        }
+        self.info
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::format_field;
+    use crate::{field_width, format_field, format_percent, format_row};
 
-    use super::{count, FileInfo};
+    use super::{aggregate, count, count_str, count_with_mode, sort_by_name, FileInfo, WordMode};
     use std::io::Cursor;
+    use unicode_segmentation::UnicodeSegmentation;
+    use unicode_width::UnicodeWidthStr;
 
     #[test]
     fn test_count() {
         let text = "I don't want the world. I just want your half.\r\n";
-        let info = count(Cursor::new(text));
+        let info = count(Cursor::new(text), false);
         let expected = FileInfo {
             num_lines: 1,
             num_words: 10,
             num_bytes: 48,
             num_chars: 48,
+            num_non_ascii: 0,
+            max_display_width: 46,
+            max_line_len: 46,
         };
         assert_eq!(info.unwrap(), expected);
     }
 
+    #[test]
+    fn test_count_any_newline() {
+        let text = "one\rtwo\r\nthree\nfour";
+        let info = count(Cursor::new(text), true);
+        let expected = FileInfo {
+            num_lines: 4,
+            num_words: 4,
+            num_bytes: 19,
+            num_chars: 19,
+            num_non_ascii: 0,
+            max_display_width: 5,
+            max_line_len: 5,
+        };
+        assert_eq!(info.unwrap(), expected);
+
+        let info = count(Cursor::new(text), false).unwrap();
+        assert_eq!(info.num_lines, 3);
+    }
+
+    #[test]
+    fn test_count_str() {
+        let text = "I don't want the world. I just want your half.\r\n";
+        assert_eq!(
+            count_str(text).unwrap(),
+            count(Cursor::new(text), false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_count_non_ascii() {
+        let info = count(Cursor::new("café\nnaïve\n"), false).unwrap();
+        assert_eq!(info.num_bytes, 13);
+        assert_eq!(info.num_chars, 11);
+        assert_eq!(info.num_non_ascii, 4);
+    }
+
+    #[test]
+    fn test_count_multibyte() {
+        // The last line has no newline but still counts in full
+        let info = count(Cursor::new("日本語 😀\nok"), false).unwrap();
+        assert_eq!(info.num_lines, 2);
+        assert_eq!(info.num_words, 3);
+        assert_eq!(info.num_bytes, 17);
+        assert_eq!(info.num_chars, 8);
+
+        let info = count(Cursor::new("cafe\u{301}"), false).unwrap();
+        assert_eq!(info.num_bytes, 6);
+        assert_eq!(info.num_chars, 5);
+    }
+
+    #[test]
+    fn test_count_unicode_words() {
+        // split_whitespace already splits on a no-break space, but only
+        // segmentation drops the free-standing dash
+        let text = "one\u{a0}two \u{2014} three";
+        let info = count(Cursor::new(text), false).unwrap();
+        assert_eq!(info.num_words, 4);
+        let info = count_with_mode(Cursor::new(text), false, WordMode::Unicode).unwrap();
+        assert_eq!(info.num_words, 3);
+    }
+
+    #[test]
+    fn test_count_long_line() {
+        // Longer than CHUNK_SIZE, with multibyte chars straddling the
+        // chunk boundaries
+        let text = "ab cdé 日本 ".repeat(10_000);
+        let info = count(Cursor::new(&text), false).unwrap();
+        let expected = FileInfo {
+            num_lines: 1,
+            num_words: 30_000,
+            num_bytes: text.len(),
+            num_chars: text.chars().count(),
+            num_non_ascii: 80_000,
+            max_display_width: text.width(),
+            max_line_len: text.chars().count(),
+        };
+        assert_eq!(info, expected);
+        let info = count_with_mode(Cursor::new(&text), true, WordMode::Unicode).unwrap();
+        assert_eq!(info.num_words, text.unicode_words().count());
+    }
+
+    #[test]
+    fn test_count_display_width_sequences() {
+        // A joined emoji is one wide glyph, so the whole line is measured
+        let text = "\n\u{301}日  👨\u{200d}👩b é";
+        let info = count(Cursor::new(text), false).unwrap();
+        assert_eq!(info.max_display_width, 9);
+
+        // Split across chunk boundaries at a space in a long line
+        let text = "a 👨\u{200d}👩 ".repeat(20_000);
+        let info = count(Cursor::new(&text), false).unwrap();
+        assert_eq!(info.max_display_width, text.width());
+    }
+
+    #[test]
+    fn test_count_display_width() {
+        let info = count(Cursor::new("abc\n日本語テキスト\r\nabcdefgh"), false).unwrap();
+        assert_eq!(info.max_display_width, 14);
+    }
+
+    #[test]
+    fn test_count_max_line_len() {
+        let info = count(Cursor::new("a\nthree\nnaïve\r\nab"), false).unwrap();
+        assert_eq!(info.max_line_len, 5);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let input = "  1  9  48 fox.txt\n\n  4 29 177\n  5 38 225 total\n";
+        assert_eq!(aggregate(Cursor::new(input)).unwrap(), (5, 38, 225));
+
+        let res = aggregate(Cursor::new("1 9 48\n1 nine 48 fox.txt\n"));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "line 2: expected \"LINES WORDS BYTES [FILE]\", found \"1 nine 48 fox.txt\""
+        );
+
+        assert!(aggregate(Cursor::new("1 9\n")).is_err());
+    }
+
+    #[test]
+    fn test_format_percent() {
+        assert_eq!(format_percent(1, 4), " 25.0%");
+        assert_eq!(format_percent(2, 3), " 66.7%");
+        assert_eq!(format_percent(5, 5), "100.0%");
+        assert_eq!(format_percent(0, 0), "  0.0%");
+    }
+
     #[test]
     fn test_format_field() {
-        assert_eq!(format_field(1, false), "");
-        assert_eq!(format_field(3, true), "       3");
-        assert_eq!(format_field(10, true), "      10");
+        assert_eq!(format_field(3, 1), "3");
+        assert_eq!(format_field(3, 2), " 3");
+        assert_eq!(format_field(10, 2), "10");
+    }
+
+    #[test]
+    fn test_field_width() {
+        assert_eq!(field_width(&[]), 1);
+        assert_eq!(field_width(&[0]), 1);
+        assert_eq!(field_width(&[7, 48, 9]), 2);
+        assert_eq!(field_width(&[1000, 5]), 4);
+    }
+
+    #[test]
+    fn test_format_row() {
+        assert_eq!(format_row(&[7], 1, "file"), "7 file");
+        assert_eq!(format_row(&[1, 9, 48], 2, "fox.txt"), " 1  9 48 fox.txt");
+        assert_eq!(format_row(&[1, 9, 48], 2, "-"), " 1  9 48");
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let info = || FileInfo {
+            num_lines: 0,
+            num_words: 0,
+            num_bytes: 0,
+            num_chars: 0,
+            num_non_ascii: 0,
+            max_display_width: 0,
+            max_line_len: 0,
+        };
+        let mut results = vec![
+            ("fox.txt".to_string(), info()),
+            ("Bustle.txt".to_string(), info()),
+            ("atlamal.txt".to_string(), info()),
+        ];
+        sort_by_name(&mut results);
+        let names: Vec<_> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["atlamal.txt", "Bustle.txt", "fox.txt"]);
     }
 }
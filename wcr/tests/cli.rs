@@ -9,6 +9,9 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const CR: &str = "tests/inputs/cr.txt";
+const UTF8: &str = "tests/inputs/utf8.txt";
+const CJK: &str = "tests/inputs/cjk.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -211,3 +214,259 @@ fn test_all_words_lines() -> TestResult {
 fn test_all_bytes_lines() -> TestResult {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn test_all_sort_name() -> TestResult {
+    run(
+        &["--sort-name", FOX, EMPTY, ATLAMAL],
+        "tests/expected/all.sort-name.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expect_counts_match() -> TestResult {
+    run(
+        &[
+            "--expect-lines",
+            "1",
+            "--expect-words",
+            "9",
+            "--expect-bytes",
+            "48",
+            FOX,
+        ],
+        "tests/expected/fox.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expect_lines_mismatch() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--expect-lines", "1", EMPTY, FOX, ATLAMAL])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(format!(
+            "{}: expected 1 lines, found 0\n{}: expected 1 lines, found 4\n",
+            EMPTY, ATLAMAL
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn cr_any_newline() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--any-newline", "-l", CR])
+        .assert()
+        .success()
+        .stdout(format!("3 {}\n", CR));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn nested_recursive() -> TestResult {
+    run(
+        &["-r", "tests/inputs/nested"],
+        "tests/expected/nested.r.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn utf8_ascii_only() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--ascii-only", UTF8])
+        .assert()
+        .success()
+        .stdout(format!(" 1  2 13  4 {}\n", UTF8));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn cjk_display_width() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-m", "--display-width", CJK])
+        .assert()
+        .success()
+        .stdout(format!(" 8 14 {}\n", CJK));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_sort_by_lines() -> TestResult {
+    // all.out lists EMPTY, FOX, ATLAMAL and the total
+    let all = fs::read_to_string("tests/expected/all.out")?;
+    let lines: Vec<_> = all.lines().collect();
+    let expected = [lines[2], lines[1], lines[0], lines[3]];
+    Command::cargo_bin(PRG)?
+        .args(["--sort-by=lines", FOX, EMPTY, ATLAMAL])
+        .assert()
+        .success()
+        .stdout(expected.join("\n") + "\n");
+
+    let expected = [lines[0], lines[1], lines[2], lines[3]];
+    Command::cargo_bin(PRG)?
+        .args(["--sort-by=lines", "--reverse", FOX, ATLAMAL, EMPTY])
+        .assert()
+        .success()
+        .stdout(expected.join("\n") + "\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_line_length() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-L"])
+        .write_stdin("short\na much longer line\n\nmid length\n")
+        .assert()
+        .success()
+        .stdout("18\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--max-line-length", FOX, ATLAMAL])
+        .assert()
+        .success()
+        .stdout(format!(" 1 47 {}\n 4 43 {}\n 5 47 total\n", FOX, ATLAMAL));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_modes() -> TestResult {
+    let all = fs::read_to_string("tests/expected/all.out")?;
+    let lines: Vec<_> = all.lines().collect();
+    let fox = format!("{}\n", lines[1]);
+    let atlamal = format!("{}\n", lines[2]);
+    let total = format!("{}\n", lines[3]);
+    for (mode, expected) in [
+        ("auto", format!("{}{}{}", fox, atlamal, total)),
+        ("always", format!("{}{}{}", fox, atlamal, total)),
+        ("only", total.clone()),
+        ("never", format!("{}{}", fox, atlamal)),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args([&format!("--total={}", mode), FOX, ATLAMAL])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    let fox_only = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--total=always", FOX])
+        .assert()
+        .success()
+        .stdout(format!("{} 1  9 48 total\n", fox_only));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn aggregate() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--aggregate")
+        .write_stdin(
+            "       1       9      48 tests/inputs/fox.txt\n\
+             \x20      4      29     177 tests/inputs/atlamal.txt\n",
+        )
+        .assert()
+        .success()
+        .stdout("  5  38 225 total\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--aggregate", "-l"])
+        .write_stdin("1 9 48\nnot wc output\n")
+        .assert()
+        .failure()
+        .stderr("line 2: expected \"LINES WORDS BYTES [FILE]\", found \"not wc output\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from() -> TestResult {
+    let all = fs::read_to_string("tests/expected/all.out")?;
+    let expected: Vec<_> = all.lines().skip(1).collect();
+    let expected = expected.join("\n") + "\n";
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "tests/inputs/files0.txt"])
+        .assert()
+        .success()
+        .stdout(expected.clone());
+
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-"])
+        .write_stdin(format!("{}\0{}", FOX, ATLAMAL))
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn single_count_minimal_width() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", FOX])
+        .assert()
+        .success()
+        .stdout(format!("1 {}\n", FOX));
+
+    Command::cargo_bin(PRG)?
+        .arg("-l")
+        .write_stdin("1\n2\n3\n4\n5\n6\n7\n")
+        .assert()
+        .success()
+        .stdout("7\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn words_unicode() -> TestResult {
+    let input = "one\u{a0}two \u{2014} three\n";
+    Command::cargo_bin(PRG)?
+        .args(["-w", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("4\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--words=unicode", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("3\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn percent() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--percent", "-l", FOX, ATLAMAL, UTF8])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let percents: Vec<f64> = stdout
+        .lines()
+        .filter(|line| !line.ends_with("total"))
+        .map(|line| line.split_whitespace().nth(1).unwrap())
+        .map(|field| field.trim_end_matches('%').parse().unwrap())
+        .collect();
+    assert_eq!(percents, [16.7, 66.7, 16.7]);
+    let sum: f64 = percents.iter().sum();
+    assert!((sum - 100.0).abs() < 0.5);
+    assert!(stdout.ends_with("6 100.0% total\n"));
+    Ok(())
+}